@@ -0,0 +1,97 @@
+// cargo bench --bench resolve
+// resolving against ~500 routes, next to a plain scan over the same patterns,
+// and hot paths with and without `Router::with_cache`
+use simple_router::{Method, Pattern, Router};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const ROUNDS: usize = 2_000;
+
+fn patterns() -> Vec<String> {
+    let mut patterns = vec![];
+    for resource in 0..100 {
+        patterns.push(format!("/api/r{}", resource));
+        patterns.push(format!("/api/r{}/:id", resource));
+        patterns.push(format!("/api/r{}/:id/edit", resource));
+        patterns.push(format!("/api/r{}/:id/items/:item", resource));
+        patterns.push(format!("/static/r{}/*path", resource));
+    }
+    patterns
+}
+
+fn time(name: &str, paths: &[&str], mut resolve: impl FnMut(&str)) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        for path in paths {
+            resolve(black_box(path));
+        }
+    }
+    let elapsed = start.elapsed();
+    let per_resolve = elapsed / (ROUNDS * paths.len()) as u32;
+    println!("{:<8} {:>10?} per resolve", name, per_resolve);
+    elapsed
+}
+
+fn main() {
+    let patterns = patterns();
+    let router = patterns.iter().fold(Router::default(), |router, pattern| {
+        router.route(Method::GET, pattern, || String::from("hit"))
+    });
+    let parsed: Vec<Pattern> = patterns
+        .iter()
+        .map(|pattern| Pattern::parse(pattern).unwrap())
+        .collect();
+    let paths = [
+        "/api/r0",
+        "/api/r50/7",
+        "/api/r99/7/edit",
+        "/api/r42/7/items/3",
+        "/static/r10/css/site.css",
+        "/missing/path",
+    ];
+    println!("{} routes", patterns.len());
+
+    // normalized once, like the router does, so only the matching differs
+    let scan = time("scan", &paths, |path| {
+        let path = Pattern::normalize(path);
+        black_box(
+            parsed
+                .iter()
+                .find_map(|pattern| pattern.matches_normalized(&path)),
+        );
+    });
+    let elapsed = time("router", &paths, |path| {
+        let _ = black_box(router.resolve(Method::GET, path));
+    });
+    println!(
+        "router is {:.1}x the scan",
+        scan.as_secs_f64() / elapsed.as_secs_f64()
+    );
+
+    // normalizing dominates for long paths sent with runs of slashes
+    let slashes = format!("/api/r42//7{}items///3//", "/".repeat(200));
+    let long = format!("/static/r10/{}", "a//b/".repeat(100));
+    time("slashes", &[&slashes, &long], |path| {
+        let _ = black_box(router.resolve(Method::GET, path));
+    });
+
+    // a leading param keeps the index from ruling routes out, so a few hot
+    // paths are matched against all of them but for the cache
+    let tenants = (0..300).fold(Router::default(), |router, resource| {
+        router.route(Method::GET, &format!("/:tenant/r{}/:id", resource), || {
+            String::from("hit")
+        })
+    });
+    let cached = tenants.clone().with_cache(64);
+    let hot = ["/acme/r299/7", "/acme/r150/7", "/globex/r0/1"];
+    let elapsed = time("tenants", &hot, |path| {
+        let _ = black_box(tenants.resolve(Method::GET, path));
+    });
+    let cached = time("cached", &hot, |path| {
+        let _ = black_box(cached.resolve(Method::GET, path));
+    });
+    println!(
+        "cached is {:.1}x uncached",
+        elapsed.as_secs_f64() / cached.as_secs_f64()
+    );
+}
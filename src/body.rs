@@ -0,0 +1,114 @@
+use std::fmt;
+
+// a response body as bytes, text or not: an image, a protobuf message, ...
+// for a `Router<Body>` or in a `Response`. text and bytes convert into one:
+// router.get("/logo.png", || Body::from(LOGO.to_vec()))
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
+pub struct Body {
+    bytes: Vec<u8>,
+}
+
+impl Body {
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    // the body as text, `None` if it isn't UTF-8
+    pub fn to_str(&self) -> Option<&str> {
+        std::str::from_utf8(&self.bytes).ok()
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+}
+
+// as a string when it's text, otherwise as bytes
+impl fmt::Debug for Body {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.to_str() {
+            Some(str) => fmt::Debug::fmt(str, f),
+            None => fmt::Debug::fmt(&self.bytes, f),
+        }
+    }
+}
+
+impl From<String> for Body {
+    fn from(body: String) -> Self {
+        Body {
+            bytes: body.into_bytes(),
+        }
+    }
+}
+
+impl From<&str> for Body {
+    fn from(body: &str) -> Self {
+        Body {
+            bytes: body.as_bytes().to_vec(),
+        }
+    }
+}
+
+impl From<Vec<u8>> for Body {
+    fn from(bytes: Vec<u8>) -> Self {
+        Body { bytes }
+    }
+}
+
+impl From<&[u8]> for Body {
+    fn from(bytes: &[u8]) -> Self {
+        Body {
+            bytes: bytes.to_vec(),
+        }
+    }
+}
+
+impl From<Body> for Vec<u8> {
+    fn from(body: Body) -> Self {
+        body.bytes
+    }
+}
+
+impl PartialEq<&str> for Body {
+    fn eq(&self, other: &&str) -> bool {
+        self.bytes == other.as_bytes()
+    }
+}
+
+impl PartialEq<&[u8]> for Body {
+    fn eq(&self, other: &&[u8]) -> bool {
+        self.bytes == *other
+    }
+}
+
+impl PartialEq<Vec<u8>> for Body {
+    fn eq(&self, other: &Vec<u8>) -> bool {
+        &self.bytes == other
+    }
+}
+
+impl PartialEq<Body> for &str {
+    fn eq(&self, other: &Body) -> bool {
+        other == self
+    }
+}
+
+impl PartialEq<Body> for &[u8] {
+    fn eq(&self, other: &Body) -> bool {
+        other == self
+    }
+}
+
+impl PartialEq<Body> for Vec<u8> {
+    fn eq(&self, other: &Body) -> bool {
+        other == self
+    }
+}
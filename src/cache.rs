@@ -0,0 +1,173 @@
+// which route a request matched, remembered by method, host and normalized
+// path, see `Router::with_cache`. only the route's index is kept: params are
+// matched again and the handler always runs. split into shards, each behind
+// its own lock, so threads resolving different paths rarely wait on another.
+// each shard evicts its own least recently used entry, so the cache as a
+// whole only approximates LRU.
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, VecDeque};
+use std::hash::BuildHasher;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+const SHARDS: usize = 16;
+
+#[derive(Debug)]
+pub(crate) struct RouteCache {
+    shards: Vec<Mutex<Shard>>,
+    hasher: RandomState,
+}
+
+// a least recently used cache: every use is queued with the shard's clock,
+// and a queued use older than its entry's last one is stale and skipped
+#[derive(Debug)]
+struct Shard {
+    capacity: usize,
+    tick: u64,
+    // key -> (route index, last use)
+    entries: HashMap<Arc<str>, (usize, u64)>,
+    // uses, the oldest first
+    uses: VecDeque<(Arc<str>, u64)>,
+}
+
+impl RouteCache {
+    // at most `capacity` entries in all, over fewer shards for a small one.
+    // the shards start empty and grow as they fill.
+    pub(crate) fn new(capacity: usize) -> Self {
+        let shards = capacity.clamp(1, SHARDS);
+        RouteCache {
+            shards: (0..shards)
+                .map(|shard| {
+                    Mutex::new(Shard {
+                        // the first shards take the remainder
+                        capacity: capacity / shards + usize::from(shard < capacity % shards),
+                        tick: 0,
+                        entries: HashMap::new(),
+                        uses: VecDeque::new(),
+                    })
+                })
+                .collect(),
+            hasher: RandomState::new(),
+        }
+    }
+
+    pub(crate) fn get(&self, key: &str) -> Option<usize> {
+        let mut shard = self.shard(key);
+        let (key, index) = shard
+            .entries
+            .get_key_value(key)
+            .map(|(key, &(index, _))| (key.clone(), index))?;
+        shard.touch(key, index);
+        Some(index)
+    }
+
+    // evicts the least recently used entry of a full shard
+    pub(crate) fn insert(&self, key: &str, index: usize) {
+        let mut shard = self.shard(key);
+        if shard.entries.len() >= shard.capacity && !shard.entries.contains_key(key) {
+            shard.evict();
+        }
+        shard.touch(key.into(), index);
+    }
+
+    fn shard_of(&self, key: &str) -> usize {
+        self.hasher.hash_one(key) as usize % self.shards.len()
+    }
+
+    fn shard(&self, key: &str) -> MutexGuard<'_, Shard> {
+        // a panic elsewhere can't leave a shard half-written, so a poisoned
+        // lock is still fine to use
+        self.shards[self.shard_of(key)]
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Shard {
+    fn touch(&mut self, key: Arc<str>, index: usize) {
+        self.tick += 1;
+        self.entries.insert(key.clone(), (index, self.tick));
+        self.uses.push_back((key, self.tick));
+        // each entry has one use that isn't stale, so dropping the stale
+        // ones at twice the capacity keeps this amortized O(1)
+        if self.uses.len() > 2 * self.capacity {
+            let entries = &self.entries;
+            self.uses
+                .retain(|(key, used)| entries.get(key).is_some_and(|&(_, last)| last == *used));
+        }
+    }
+
+    fn evict(&mut self) {
+        while let Some((key, used)) = self.uses.pop_front() {
+            if self
+                .entries
+                .get(&key)
+                .is_some_and(|&(_, last)| last == used)
+            {
+                self.entries.remove(&key);
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn least_recently_used_is_evicted() {
+        let cache = RouteCache::new(1);
+        let keys = [String::from("GET /0"), String::from("GET /1")];
+
+        cache.insert("GET /a", 1);
+        assert_eq!(Some(1), cache.get("GET /a"));
+        cache.insert(&keys[0], 2);
+        assert_eq!(None, cache.get("GET /a"));
+        assert_eq!(Some(2), cache.get(&keys[0]));
+        cache.insert(&keys[0], 3);
+        assert_eq!(Some(3), cache.get(&keys[0]));
+        cache.insert(&keys[1], 4);
+        assert_eq!(None, cache.get(&keys[0]));
+        assert_eq!(None, cache.get("GET /missing"));
+    }
+
+    #[test]
+    fn a_recent_use_outlives_an_older_insert() {
+        let cache = RouteCache::new(SHARDS * 2);
+        // two entries per shard, so find three keys sharing one
+        let keys: Vec<String> = (0..)
+            .map(|i| format!("GET /{}", i))
+            .filter(|key| cache.shard_of(key) == 0)
+            .take(3)
+            .collect();
+
+        cache.insert(&keys[0], 0);
+        cache.insert(&keys[1], 1);
+        for _ in 0..10 {
+            assert_eq!(Some(0), cache.get(&keys[0]));
+        }
+        cache.insert(&keys[2], 2);
+        assert_eq!(Some(0), cache.get(&keys[0]));
+        assert_eq!(None, cache.get(&keys[1]));
+        assert_eq!(Some(2), cache.get(&keys[2]));
+        let shard = cache.shards[0].lock().unwrap();
+        assert!(shard.uses.len() <= 2 * shard.capacity);
+    }
+
+    #[test]
+    fn capacity_bounds_every_shard_together() {
+        for capacity in [1, 5, SHARDS, SHARDS + 3, 100] {
+            let cache = RouteCache::new(capacity);
+            for i in 0..capacity * 10 {
+                cache.insert(&format!("GET /{}", i), i);
+            }
+            let len: usize = cache
+                .shards
+                .iter()
+                .map(|shard| shard.lock().unwrap().entries.len())
+                .sum();
+            assert!(len <= capacity, "{} > {}", len, capacity);
+            assert!(cache.shards.len() <= capacity);
+        }
+    }
+}
@@ -0,0 +1,115 @@
+// static files, with the `fs` feature:
+// Router::default().serve_dir("/assets", "./public")
+use crate::pattern::percent_decode;
+use crate::{Body, Params, Response, RouteError, Router};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+impl<S: Clone> Router<Response, S> {
+    // a GET route for everything under `url_prefix`, answered from the file
+    // at the rest of the path under `dir`. a directory answers with its
+    // `index.html`. a missing file, a directory without one, or a path that
+    // leads out of `dir`, e.g. through a symlink, is a 404. `dir` is looked
+    // up on every request, so it needn't exist yet.
+    pub fn serve_dir(self, url_prefix: &str, dir: impl AsRef<Path>) -> Self {
+        let dir = dir.as_ref().to_path_buf();
+        let pattern = format!("{}/**", url_prefix.trim_end_matches('/'));
+        self.get(&pattern, move |params: &Params| {
+            let rest = params.wildcards().last().unwrap_or_default();
+            match read_file(&dir, rest) {
+                Some((path, contents)) => file_response(&path, contents),
+                None => Response::from(RouteError::NotFound),
+            }
+        })
+    }
+}
+
+fn read_file(dir: &Path, rest: &str) -> Option<(PathBuf, Vec<u8>)> {
+    let dir = dir.canonicalize().ok()?;
+    let mut path = dir.clone();
+    for segment in rest.split('/').filter(|segment| !segment.is_empty()) {
+        path.push(&*percent_decode(segment));
+    }
+    let mut path = path.canonicalize().ok()?;
+    if !path.starts_with(&dir) {
+        return None;
+    }
+    if path.is_dir() {
+        path.push("index.html");
+    }
+    let contents = fs::read(&path).ok()?;
+    Some((path, contents))
+}
+
+// by extension, anything else is text or bytes like any `Body`
+fn file_response(path: &Path, contents: Vec<u8>) -> Response {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+    let content_type = match extension.as_deref() {
+        Some("html" | "htm") => "text/html",
+        Some("css") => "text/css",
+        Some("js" | "mjs") => "text/javascript",
+        Some("json") => "application/json",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("ico") => "image/x-icon",
+        Some("wasm") => "application/wasm",
+        _ => return Response::from(Body::from(contents)),
+    };
+    Response::status(200)
+        .with_header("Content-Type", content_type)
+        .with_body(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serves_files_inside_the_dir_only() {
+        let root = std::env::temp_dir().join(format!("simple-router-fs-{}", std::process::id()));
+        let public = root.join("public");
+        fs::create_dir_all(public.join("css/empty")).unwrap();
+        fs::write(public.join("index.html"), "<h1>home</h1>").unwrap();
+        fs::write(public.join("css/site.css"), "body {}").unwrap();
+        fs::write(public.join("logo.bin"), [0u8, 159, 146, 150]).unwrap();
+        fs::write(root.join("secret.txt"), "secret").unwrap();
+
+        let router = Router::default().serve_dir("/assets/", &public);
+        let get = |path| router.resolve_response("GET", path);
+
+        let response = get("/assets/css/site.css");
+        assert_eq!(
+            (200, "body {}"),
+            (response.status, response.body.to_str().unwrap())
+        );
+        assert_eq!(Some("text/css"), response.header("content-type"));
+        let response = get("/assets/logo.bin");
+        assert_eq!(vec![0u8, 159, 146, 150], response.body);
+        assert_eq!(
+            Some("application/octet-stream"),
+            response.header("content-type")
+        );
+        for path in ["/assets", "/assets/", "/assets/%63ss/../index.html"] {
+            let response = get(path);
+            assert_eq!("<h1>home</h1>", response.body, "{}", path);
+            assert_eq!(Some("text/html"), response.header("content-type"));
+        }
+
+        for path in [
+            "/assets/missing.css",
+            "/assets/css/empty",
+            "/assets/../secret.txt",
+            "/assets/%2e%2e/secret.txt",
+            "/assets/..%2Fsecret.txt",
+            "/assets/%2F..%2Fsecret.txt",
+        ] {
+            assert_eq!(404, get(path).status, "{}", path);
+        }
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,58 @@
+// ready-made handlers for the bodies every small server repeats:
+// router.get("/health", handlers::text("OK"))
+use crate::{Handler, Request};
+use std::sync::Arc;
+
+// always answers with `body`
+pub fn text(body: &str) -> Handler<String> {
+    let body = body.to_string();
+    Arc::new(move |_: &Request| body.clone())
+}
+
+// always answers with a status code and `body`, for a `Router<(u16, String)>`
+pub fn status(code: u16, body: &str) -> Handler<(u16, String)> {
+    let body = body.to_string();
+    Arc::new(move |_: &Request| (code, body.clone()))
+}
+
+pub fn ok() -> Handler<(u16, String)> {
+    status(200, "OK")
+}
+
+pub fn not_found() -> Handler<(u16, String)> {
+    status(404, "Not Found")
+}
+
+pub fn method_not_allowed() -> Handler<(u16, String)> {
+    status(405, "Method Not Allowed")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Method, Router};
+
+    #[test]
+    fn ready_made_handlers() {
+        let router = Router::default()
+            .get("/health", text("OK"))
+            .get("/", || String::from("home"));
+        assert_eq!("OK", router.resolve("GET", "/health").unwrap());
+
+        let fallback = not_found();
+        let router = Router::default()
+            .get("/", ok())
+            .post("/", status(201, "Created"))
+            .delete("/", method_not_allowed());
+        let resolve = |method, path| {
+            router.resolve_or(method, path, || fallback(&Request::new(Method::GET, path)))
+        };
+        assert_eq!((200, String::from("OK")), resolve("GET", "/"));
+        assert_eq!((201, String::from("Created")), resolve("POST", "/"));
+        assert_eq!(
+            (405, String::from("Method Not Allowed")),
+            resolve("DELETE", "/")
+        );
+        assert_eq!((404, String::from("Not Found")), resolve("GET", "/missing"));
+    }
+}
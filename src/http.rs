@@ -0,0 +1,137 @@
+// `http` crate interop, with the `http` feature:
+// router.resolve_http(&http::Request::get("/users/1").body(()).unwrap())
+use crate::{Method, Request, Response, Router};
+
+// every `http::Method` is a valid token, the ones without a variant become
+// `Method::Custom`
+impl From<::http::Method> for Method {
+    fn from(method: ::http::Method) -> Self {
+        Method::from(&method)
+    }
+}
+
+impl From<&::http::Method> for Method {
+    fn from(method: &::http::Method) -> Self {
+        Method::try_from(method.as_str())
+            .unwrap_or_else(|_| Method::Custom(method.as_str().to_ascii_uppercase()))
+    }
+}
+
+impl<T: Into<Response>, S: Clone> Router<T, S> {
+    // like `Router<Response>::resolve_response`, for an `http::Request`. the
+    // path and query come from the URI, the host from the URI or the `Host`
+    // header for `route_on_host`, and headers are passed on; the body isn't.
+    pub fn resolve_http<B>(&self, request: &::http::Request<B>) -> ::http::Response<Vec<u8>> {
+        let uri = request.uri();
+        let mut resolved = Request::new(request.method().into(), uri.path());
+        resolved.query = uri.query().map(String::from);
+        for (name, value) in request.headers() {
+            if let Ok(value) = value.to_str() {
+                resolved = resolved.with_header(name.as_str(), value);
+            }
+        }
+        let host = uri
+            .host()
+            .or_else(|| resolved.header("host"))
+            .map(String::from);
+
+        let response = match self.resolve_in(resolved, host.as_deref()) {
+            Ok(body) => body.into(),
+            Err(error) => Response::from(error),
+        };
+        to_http(response)
+    }
+}
+
+impl From<Response> for ::http::Response<Vec<u8>> {
+    fn from(response: Response) -> Self {
+        to_http(response)
+    }
+}
+
+// a status outside 100..=999 becomes a 500, a header `http` refuses is
+// dropped
+fn to_http(response: Response) -> ::http::Response<Vec<u8>> {
+    let mut builder = ::http::Response::builder().status(
+        ::http::StatusCode::from_u16(response.status)
+            .unwrap_or(::http::StatusCode::INTERNAL_SERVER_ERROR),
+    );
+    for (name, value) in response.headers() {
+        if let (Ok(name), Ok(value)) = (
+            ::http::HeaderName::try_from(name),
+            ::http::HeaderValue::try_from(value),
+        ) {
+            builder = builder.header(name, value);
+        }
+    }
+    builder.body(response.body.into_bytes()).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Params;
+
+    #[test]
+    fn http_methods_convert() {
+        assert_eq!(Method::GET, Method::from(::http::Method::GET));
+        assert_eq!(Method::PATCH, Method::from(&::http::Method::PATCH));
+        assert_eq!(Method::TRACE, Method::from(::http::Method::TRACE));
+        let purge = ::http::Method::from_bytes(b"PURGE").unwrap();
+        assert_eq!(Method::Custom(String::from("PURGE")), Method::from(purge));
+    }
+
+    #[test]
+    fn resolve_http_requests() {
+        let router = Router::default()
+            .get("/users/:id", |params: &Params| {
+                format!("user {}", params.get("id").unwrap())
+            })
+            .get("/search", |request: &Request| {
+                format!(
+                    "{} {}",
+                    request.query_params().get("q").unwrap(),
+                    request.header("accept").unwrap()
+                )
+            })
+            .route_on_host("api.example.com", Method::GET, "/", || String::from("api"));
+        let request = |method: ::http::Method, uri: &str| {
+            ::http::Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("Accept", "text/plain")
+                .body(())
+                .unwrap()
+        };
+
+        let response = router.resolve_http(&request(::http::Method::GET, "/users/7"));
+        assert_eq!(::http::StatusCode::OK, response.status());
+        assert_eq!(b"user 7", response.body().as_slice());
+        assert_eq!("text/plain", response.headers()["content-type"]);
+        assert_eq!(
+            b"rust text/plain",
+            router
+                .resolve_http(&request(::http::Method::GET, "/search?q=rust"))
+                .body()
+                .as_slice()
+        );
+
+        let response = router.resolve_http(&request(::http::Method::GET, "/missing"));
+        assert_eq!(::http::StatusCode::NOT_FOUND, response.status());
+        let response = router.resolve_http(&request(::http::Method::DELETE, "/users/7"));
+        assert_eq!(::http::StatusCode::METHOD_NOT_ALLOWED, response.status());
+        assert_eq!("GET", response.headers()["allow"]);
+        let trace = router.resolve_http(&request(::http::Method::TRACE, "/users/7"));
+        assert_eq!(::http::StatusCode::METHOD_NOT_ALLOWED, trace.status());
+
+        let api = ::http::Request::get("/")
+            .header("Host", "API.example.com:8080")
+            .body(())
+            .unwrap();
+        assert_eq!(b"api", router.resolve_http(&api).body().as_slice());
+        let api = ::http::Request::get("http://api.example.com/")
+            .body(())
+            .unwrap();
+        assert_eq!(b"api", router.resolve_http(&api).body().as_slice());
+    }
+}
@@ -0,0 +1,124 @@
+// routes grouped in a trie by the literal segments their patterns start
+// with. a resolve walks the path down the trie and only tries the routes met
+// on the way, so its cost follows the path and the routes sharing its
+// prefix, not every route registered. the full match is still
+// `Pattern::match_path`, the trie only rules routes out.
+use crate::pattern::{eq_static, percent_decode, Pattern};
+use crate::Options;
+use std::collections::HashMap;
+
+#[derive(Debug, Default)]
+pub(crate) struct RouteIndex {
+    root: TrieNode,
+}
+
+#[derive(Debug, Default)]
+struct TrieNode {
+    // routes whose literal prefix ends here, by registration index
+    routes: Vec<usize>,
+    // keyed by `key`
+    children: HashMap<String, TrieNode>,
+}
+
+impl RouteIndex {
+    pub(crate) fn new<'a>(patterns: impl Iterator<Item = &'a Pattern>, options: &Options) -> Self {
+        let mut index = RouteIndex::default();
+        for (route, pattern) in patterns.enumerate() {
+            let mut node = &mut index.root;
+            for segment in pattern.static_prefix() {
+                // see `key`, such a segment has to be compared one by one
+                let Some(key) = key(segment, options) else {
+                    break;
+                };
+                node = node.children.entry(key).or_default();
+            }
+            node.routes.push(route);
+        }
+        index
+    }
+
+    // the routes that can match the normalized `path`, in registration order
+    pub(crate) fn candidates(&self, path: &str, options: &Options) -> Vec<usize> {
+        let mut routes = vec![];
+        let mut node = &self.root;
+        let mut segments = path.split('/');
+        loop {
+            routes.extend(&node.routes);
+            let Some(segment) = segments.next() else {
+                break;
+            };
+            let segment = percent_decode(segment);
+            let child = match key(&segment, options) {
+                Some(key) => node.children.get(&key),
+                None => node
+                    .children
+                    .iter()
+                    .find(|(key, _)| eq_static(key, &segment, options))
+                    .map(|(_, child)| child),
+            };
+            match child {
+                Some(child) => node = child,
+                None => break,
+            }
+        }
+        routes.sort_unstable();
+        routes
+    }
+}
+
+// equal keys for segments `eq_static` finds equal. full Unicode folding can
+// turn a non-ASCII segment into an ASCII one, `Straße` matches `STRASSE`, so
+// with the `unicode` feature such segments have no key.
+fn key(segment: &str, options: &Options) -> Option<String> {
+    if !options.case_insensitive {
+        return Some(segment.to_string());
+    }
+    if cfg!(feature = "unicode") && !segment.is_ascii() {
+        return None;
+    }
+    Some(segment.to_ascii_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index(patterns: &[&str], options: &Options) -> RouteIndex {
+        let patterns: Vec<Pattern> = patterns
+            .iter()
+            .map(|pattern| Pattern::parse(pattern).unwrap())
+            .collect();
+        RouteIndex::new(patterns.iter(), options)
+    }
+
+    #[test]
+    fn candidates_share_the_path_prefix() {
+        let options = Options::default();
+        let index = index(
+            &[
+                "/users/:id",
+                "/posts",
+                "/users/me",
+                "/:any",
+                "/users/:id/posts",
+                "/**",
+            ],
+            &options,
+        );
+        assert_eq!(vec![0, 2, 3, 4, 5], index.candidates("/users/me", &options));
+        assert_eq!(vec![1, 3, 5], index.candidates("/posts/1", &options));
+        assert_eq!(vec![3, 5], index.candidates("/USERS/me", &options));
+        assert_eq!(vec![3, 5], index.candidates("", &options));
+    }
+
+    #[test]
+    fn case_insensitive_keys() {
+        let options = Options {
+            case_insensitive: true,
+            ..Options::default()
+        };
+        let index = index(&["/Users/:id", "/posts"], &options);
+        assert_eq!(vec![0], index.candidates("/USERS/me", &options));
+        assert_eq!(vec![1], index.candidates("/Posts", &options));
+    }
+}
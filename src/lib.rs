@@ -1,168 +1,5350 @@
+pub use body::Body;
+use cache::RouteCache;
 use derive_new::new;
+use index::RouteIndex;
+pub use pattern::{Pattern, PatternError};
+pub use request::{Query, Request};
+pub use response::Response;
+#[cfg(feature = "serde")]
+pub use spec::{RouteSpec, RouteSpecEntry};
+use std::cell::RefCell;
+use std::convert::Infallible;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
 
-#[derive(Default, Debug)]
-pub struct Router {
-    nodes: Vec<Node>,
+mod body;
+mod cache;
+#[cfg(feature = "fs")]
+mod fs;
+pub mod handlers;
+#[cfg(feature = "http")]
+mod http;
+mod index;
+mod pattern;
+mod request;
+mod response;
+#[cfg(feature = "server")]
+mod server;
+#[cfg(feature = "serde")]
+mod spec;
+#[cfg(feature = "test-util")]
+pub mod testing;
+
+// `T` is what handlers return. builders take the router by value and hand
+// it back, so chaining `router.get(..).post(..)` never copies the routes.
+// a router is `Send + Sync` and resolves through `&self` without locking,
+// so one built at startup can serve every thread. cloning one, e.g. per
+// worker, shares its routes and index until a copy is changed.
+// `S` is state handlers can borrow, see `with_state`.
+pub struct Router<T = String, S = ()> {
+    nodes: Arc<Vec<Node<T>>>,
+    state: S,
+    options: Options,
+    maintenance: Option<Handler<T>>,
+    fallback: Option<Arc<Router<T, S>>>,
+    not_found: Option<Handler<T>>,
+    method_not_allowed: Option<Handler<T>>,
+    // builds the body of an automatic OPTIONS answer, see `auto_options`
+    auto_options: Option<Arc<dyn Fn(String) -> T + Send + Sync>>,
+    middleware: Vec<Middleware<T>>,
+    // see `route_fallible`
+    error_handler: Option<ErrorHandler<T>>,
+    on_match: Vec<MatchHook>,
+    on_no_match: Vec<NoMatchHook>,
+    // built on the first resolve, and anew once the routes change
+    index: Arc<OnceLock<RouteIndex>>,
+    // see `with_cache`, 0 for none
+    cache_capacity: usize,
+    // built on the first resolve like `index`, `None` when guards rule it out
+    cache: Arc<OnceLock<Option<RouteCache>>>,
+}
+
+impl<T> Default for Router<T> {
+    fn default() -> Self {
+        Self::with_state(())
+    }
+}
+
+impl<T, S> Router<T, S> {
+    // a router whose handlers can borrow `state`, a pool, a config, ...:
+    // Router::with_state(db).get("/users", |db: &Db| db.users())
+    // each handler taking `&S` gets its own clone as it's registered, so
+    // anything they should share, a counter or a cache, goes in an `Arc`.
+    // `Router::default()` has `()` for state.
+    pub fn with_state(state: S) -> Self {
+        Self {
+            nodes: Arc::default(),
+            state,
+            options: Options::default(),
+            maintenance: None,
+            fallback: None,
+            not_found: None,
+            method_not_allowed: None,
+            auto_options: None,
+            middleware: vec![],
+            error_handler: None,
+            on_match: vec![],
+            on_no_match: vec![],
+            index: Arc::default(),
+            cache_capacity: 0,
+            cache: Arc::default(),
+        }
+    }
+}
+
+impl<T, S: Clone> Clone for Router<T, S> {
+    fn clone(&self) -> Self {
+        Self {
+            nodes: self.nodes.clone(),
+            state: self.state.clone(),
+            options: self.options,
+            maintenance: self.maintenance.clone(),
+            fallback: self.fallback.clone(),
+            not_found: self.not_found.clone(),
+            method_not_allowed: self.method_not_allowed.clone(),
+            auto_options: self.auto_options.clone(),
+            middleware: self.middleware.clone(),
+            error_handler: self.error_handler.clone(),
+            on_match: self.on_match.clone(),
+            on_no_match: self.on_no_match.clone(),
+            index: self.index.clone(),
+            cache_capacity: self.cache_capacity,
+            cache: self.cache.clone(),
+        }
+    }
+}
+
+impl<T, S> fmt::Debug for Router<T, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Router")
+            .field("nodes", &self.nodes)
+            .field("options", &self.options)
+            .field("maintenance", &self.maintenance.is_some())
+            .field("fallback", &self.fallback)
+            .field("not_found", &self.not_found.is_some())
+            .field("method_not_allowed", &self.method_not_allowed.is_some())
+            .field("auto_options", &self.auto_options.is_some())
+            .field("middleware", &self.middleware.len())
+            .field("error_handler", &self.error_handler.is_some())
+            .field("on_match", &self.on_match.len())
+            .field("on_no_match", &self.on_no_match.len())
+            .field("cache_capacity", &self.cache_capacity)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Options {
+    case_insensitive: bool,
+    strict: bool,
+    unknown_method: UnknownMethod,
+    on_resolve: Option<fn(&ResolveInfo)>,
+    maintenance: bool,
+    head_fallback: bool,
+    strict_slashes: bool,
+    preserve_double_slashes: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            case_insensitive: false,
+            strict: false,
+            unknown_method: UnknownMethod::default(),
+            on_resolve: None,
+            maintenance: false,
+            head_fallback: true,
+            strict_slashes: false,
+            preserve_double_slashes: false,
+        }
+    }
+}
+
+// passed to the `Router::on_resolve` callback
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolveInfo<'a> {
+    // as given to `resolve`, before parsing and normalization. the path
+    // is without its query
+    pub method: &'a str,
+    pub path: &'a str,
+    // `None` on a miss
+    pub pattern: Option<&'a str>,
+    // including the handler
+    pub elapsed: Duration,
+}
+
+// passed to `Router::on_match` hooks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatchInfo<'a> {
+    pub method: &'a str,
+    // normalized, without the query
+    pub path: &'a str,
+    // as registered, after trailing slash trimming
+    pub pattern: &'a str,
+    // of the handler and middleware alone
+    pub elapsed: Duration,
+}
+
+// see `Router::on_match` and `Router::on_no_match`
+pub type MatchHook = Arc<dyn Fn(&MatchInfo) + Send + Sync>;
+pub type NoMatchHook = Arc<dyn Fn(&str, &str) + Send + Sync>;
+
+// a miss an `or_else` fallback gets to answer
+fn is_miss<T>(result: &Result<Match<T>, RouteError>) -> bool {
+    matches!(
+        result,
+        Err(RouteError::NotFound | RouteError::MethodNotAllowed(_) | RouteError::NotImplemented(_))
+    )
+}
+
+// how `Router::resolve_full` reports a method no route is registered with
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnknownMethod {
+    // `RouteError::NotFound` or `RouteError::MethodNotAllowed`, like any
+    // other miss
+    #[default]
+    NotFound,
+    // `RouteError::NotImplemented`, i.e. a 501
+    NotImplemented,
+}
+
+impl<T: 'static, S: Clone> Router<T, S> {
+    pub fn route<M>(
+        self,
+        method: Method,
+        pattern: &str,
+        handler: impl IntoHandler<T, M, S>,
+    ) -> Self {
+        let handler = infallible(handler.into_handler(&self.state));
+        self.push_node(None, method.into(), pattern, handler)
+    }
+
+    // like `route`, for a handler returning `Result<T, E>`. an error is
+    // turned into the body by `error_handler`, or without one into
+    // "internal server error" through `From<String>`. middleware sees that
+    // body like any other, and `Match::error` says the handler failed:
+    // router.get_fallible("/users/:id", |params: &Params| db.user(params))
+    pub fn route_fallible<M, E>(
+        self,
+        method: Method,
+        pattern: &str,
+        handler: impl IntoHandler<Result<T, E>, M, S>,
+    ) -> Self
+    where
+        T: From<String>,
+        E: Into<HandlerError> + 'static,
+    {
+        let handler = handler.into_handler(&self.state);
+        let handler: RouteHandler<T> =
+            Arc::new(move |request: &Request, recover: &Recover<'_, T>| {
+                handler(request).unwrap_or_else(|error| {
+                    recover
+                        .recover(error.into())
+                        .unwrap_or_else(|| T::from(String::from("internal server error")))
+                })
+            });
+        self.push_node(None, method.into(), pattern, handler)
+    }
+
+    // turns the error of a `route_fallible` handler into its body. a second
+    // call replaces the handler. like middleware it applies to every route
+    // of this router, `merge`d ones included, but not to a fallback's.
+    pub fn error_handler(
+        mut self,
+        handler: impl Fn(&(dyn Error + 'static)) -> T + Send + Sync + 'static,
+    ) -> Self {
+        self.error_handler = Some(Arc::new(handler));
+        self
+    }
+
+    // like `route`, for every method. a route for the method itself wins
+    // over it whatever the order they were registered in, see `find`.
+    pub fn any<M>(self, pattern: &str, handler: impl IntoHandler<T, M, S>) -> Self {
+        let handler = infallible(handler.into_handler(&self.state));
+        self.push_node(None, MethodMatcher::Any, pattern, handler)
+    }
+
+    // like `route` when `cond` holds, otherwise the router is returned as is
+    pub fn route_if<M>(
+        self,
+        cond: bool,
+        method: Method,
+        pattern: &str,
+        handler: impl IntoHandler<T, M, S>,
+    ) -> Self {
+        if cond {
+            self.route(method, pattern, handler)
+        } else {
+            self
+        }
+    }
+
+    // one handler under several aliases, one route per pattern:
+    // patterns(Method::GET, &["/", "/home", "/index"], home)
+    pub fn patterns<M>(
+        self,
+        method: Method,
+        patterns: &[&str],
+        handler: impl IntoHandler<T, M, S>,
+    ) -> Self {
+        let handler = infallible(handler.into_handler(&self.state));
+        patterns.iter().fold(self, |router, pattern| {
+            router.push_node(None, method.clone().into(), pattern, handler.clone())
+        })
+    }
+
+    // like `route`, but returns an error instead of panicking on a malformed
+    // pattern, see `Pattern::parse`, and refuses a route an earlier one would
+    // shadow, see `conflicts`. `route` itself keeps the first registered and
+    // leaves the later one dead.
+    pub fn try_route<M>(
+        self,
+        method: Method,
+        pattern: &str,
+        handler: impl IntoHandler<T, M, S>,
+    ) -> Result<Self, TryRouteError> {
+        let pattern = Pattern::parse(pattern)?;
+        let node = Node::new(
+            method.into(),
+            pattern,
+            infallible(handler.into_handler(&self.state)),
+            None,
+        );
+        let second = self.nodes.len();
+        if let Some((first, existing)) = self
+            .nodes
+            .iter()
+            .enumerate()
+            .find(|(_, existing)| self.shadows(existing, &node))
+        {
+            return Err(Conflict::new(first, existing, second, &node).into());
+        }
+        let mut router = self;
+        router.nodes_mut().push(node);
+        Ok(router)
+    }
+
+    // like `route`, but only for requests to `host`, see `resolve_with_host`.
+    // hosts compare case-insensitively, and a leading `*` label matches
+    // exactly one label, captured as the `subdomain` param:
+    // *.example.com -> a.example.com, not example.com or a.b.example.com
+    pub fn route_on_host<M>(
+        self,
+        host: &str,
+        method: Method,
+        pattern: &str,
+        handler: impl IntoHandler<T, M, S>,
+    ) -> Self {
+        let host = host.to_ascii_lowercase();
+        let handler = infallible(handler.into_handler(&self.state));
+        self.push_node(Some(host), method.into(), pattern, handler)
+    }
+
+    fn push_node(
+        mut self,
+        host: Option<String>,
+        method: MethodMatcher,
+        pattern: &str,
+        handler: RouteHandler<T>,
+    ) -> Self {
+        let pattern = Pattern::parse(pattern).unwrap_or_else(|e| panic!("{}", e));
+        self.nodes_mut()
+            .push(Node::new(method, pattern, handler, host));
+        self
+    }
+
+    // the routes, for changing them: copied first if a clone shares them,
+    // and the index is built anew on the next resolve
+    fn nodes_mut(&mut self) -> &mut Vec<Node<T>> {
+        self.invalidate();
+        Arc::make_mut(&mut self.nodes)
+    }
+
+    // drops what was worked out from the routes and options, the index and
+    // the cache, for a change to either
+    fn invalidate(&mut self) {
+        self.index = Arc::default();
+        self.cache = Arc::default();
+    }
+
+    // remembers which route up to `capacity` recently resolved paths
+    // matched, by method, host and normalized path, so a hot path skips
+    // matching against every candidate route. the entries are split over
+    // shards, each evicting its own least recently used, so it's roughly the
+    // most recent ones that are kept. the handler still runs on
+    // every resolve. changing the routes or the options empties it. a
+    // router with `guard`s doesn't use it, since a guard looks at more than
+    // the path. 0 turns it off.
+    pub fn with_cache(mut self, capacity: usize) -> Self {
+        self.cache_capacity = capacity;
+        self.cache = Arc::default();
+        self
+    }
+
+    // removes every route for `method` registered under `pattern`, on any
+    // host, compared as `route` stores it: /users/ removes /users. returns
+    // whether there was one. `Match::index` counts the routes that are left.
+    pub fn remove(&mut self, method: Method, pattern: &str) -> bool {
+        let len = self.nodes.len();
+        let Some(pattern) = self.find_pattern(&method, pattern) else {
+            return false;
+        };
+        self.nodes_mut()
+            .retain(|node| !(node.method == method && node.pattern.as_str() == pattern));
+        self.nodes.len() < len
+    }
+
+    // swaps the handler of every route `remove` would remove, keeping its
+    // place, name, guards and the rest. returns whether there was one.
+    pub fn replace<M>(
+        &mut self,
+        method: Method,
+        pattern: &str,
+        handler: impl IntoHandler<T, M, S>,
+    ) -> bool {
+        let Some(pattern) = self.find_pattern(&method, pattern) else {
+            return false;
+        };
+        let handler = infallible(handler.into_handler(&self.state));
+        for node in self.nodes_mut() {
+            if node.method == method && node.pattern.as_str() == pattern {
+                node.handler = handler.clone();
+            }
+        }
+        true
+    }
+
+    // `pattern` as stored, if a route for `method` has it
+    fn find_pattern(&self, method: &Method, pattern: &str) -> Option<String> {
+        let pattern = Pattern::parse(pattern).ok()?;
+        self.nodes
+            .iter()
+            .any(|node| &node.method == method && node.pattern.as_str() == pattern.as_str())
+            .then(|| pattern.as_str().to_string())
+    }
+
+    // removes every route, keeping the options and the other handlers
+    pub fn clear(&mut self) {
+        self.nodes_mut().clear();
+    }
+
+    // compare literal segments ignoring case. only ASCII letters fold by
+    // default; with the `unicode` feature full Unicode case folding is used,
+    // so /Café matches /CAFÉ and /Straße matches /STRASSE.
+    // params keep the casing of the request.
+    pub fn case_insensitive(mut self, on: bool) -> Self {
+        self.options.case_insensitive = on;
+        self.invalidate();
+        self
+    }
+
+    // every pattern segment has to consume at least one path segment, so a
+    // `**` no longer matches an empty rest: /files/** matches /files/a but
+    // not /files
+    pub fn strict(mut self, on: bool) -> Self {
+        self.options.strict = on;
+        self.invalidate();
+        self
+    }
+
+    // a trailing slash tells routes apart instead of being trimmed: /foo/
+    // only matches a route registered as /foo/, /foo only /foo. / is a
+    // route of its own either way.
+    pub fn strict_slashes(mut self, on: bool) -> Self {
+        self.options.strict_slashes = on;
+        self.invalidate();
+        self
+    }
+
+    // keeps // in request paths, as an empty segment, instead of collapsing
+    // it: /a//b no longer matches /a/b, e.g. to keep signed URLs intact
+    pub fn preserve_double_slashes(mut self, on: bool) -> Self {
+        self.options.preserve_double_slashes = on;
+        self.invalidate();
+        self
+    }
+
+    // a miss here (404, 405 or 501) is retried against `fallback`, and so on
+    // down the chain, each router with its own options. a match in a
+    // fallback counts its `Match::index` on from the routers before it.
+    // a 405 anywhere in the chain beats a 404, listing the methods of every
+    // router. a `not_found` handler or `resolve_or` default only answers
+    // once, after the last router missed.
+    pub fn or_else(mut self, fallback: Router<T, S>) -> Self {
+        self.fallback = Some(Arc::new(match self.fallback.take() {
+            Some(existing) => Arc::unwrap_or_clone(existing).or_else(fallback),
+            None => fallback,
+        }));
+        self
+    }
+
+    // answers misses in place of `RouteError::NotFound` (or a `resolve_or`
+    // default), with the method and path that missed. `resolve_full` still
+    // reports the error. in an `or_else` chain only the last
+    // router's handler fires. a second call replaces the handler, and
+    // routers brought in with `merge` or `scope` leave theirs behind.
+    pub fn not_found<M>(mut self, handler: impl IntoHandler<T, M, S>) -> Self {
+        self.not_found = Some(handler.into_handler(&self.state));
+        self
+    }
+
+    // like `not_found`, for a `RouteError::MethodNotAllowed`
+    pub fn method_not_allowed<M>(mut self, handler: impl IntoHandler<T, M, S>) -> Self {
+        self.method_not_allowed = Some(handler.into_handler(&self.state));
+        self
+    }
+
+    // every request is answered by `handler`, without matching any route,
+    // until `set_maintenance(false)`. resolving returns its body, never a
+    // miss or a `resolve_or` default meanwhile, and `resolve_full` reports
+    // `RouteError::Unavailable`.
+    pub fn maintenance<M>(mut self, handler: impl IntoHandler<T, M, S>) -> Self {
+        self.maintenance = Some(handler.into_handler(&self.state));
+        self.options.maintenance = true;
+        self
+    }
+
+    // switches maintenance mode on or off in place, keeping the routes and
+    // the handler from `maintenance`. without a handler this does nothing.
+    pub fn set_maintenance(&mut self, on: bool) {
+        self.options.maintenance = on;
+    }
+
+    // called once at the end of every resolve, matched or not
+    pub fn on_resolve(mut self, callback: fn(&ResolveInfo)) -> Self {
+        self.options.on_resolve = Some(callback);
+        self
+    }
+
+    // called with every match, after the handler ran, see `MatchInfo`.
+    // hooks run in the order they were added, on the thread resolving. a
+    // panicking hook panics the resolve, like a panicking handler would,
+    // and leaves the router as it was.
+    // in an `or_else` chain only this router's hooks are called, for a
+    // match in any router of the chain.
+    pub fn on_match(mut self, hook: impl Fn(&MatchInfo) + Send + Sync + 'static) -> Self {
+        self.on_match.push(Arc::new(hook));
+        self
+    }
+
+    // like `on_match`, for a resolve nothing matched: a 404, 405 or 501
+    // before any `not_found` or `method_not_allowed` handler answers it.
+    // called with the method and the path as sent.
+    pub fn on_no_match(mut self, hook: impl Fn(&str, &str) + Send + Sync + 'static) -> Self {
+        self.on_no_match.push(Arc::new(hook));
+        self
+    }
+
+    // on by default: a HEAD request runs the GET route when no HEAD route
+    // for the same pattern is registered. the body is returned as usual,
+    // dropping it is up to the caller, as `listen` does.
+    pub fn head_fallback(mut self, on: bool) -> Self {
+        self.options.head_fallback = on;
+        self.invalidate();
+        self
+    }
+
+    pub fn unknown_method(mut self, policy: UnknownMethod) -> Self {
+        self.options.unknown_method = policy;
+        self
+    }
+
+    // like `route`, but `transform` post-processes the handler's output
+    pub fn route_map<M>(
+        self,
+        method: Method,
+        pattern: &str,
+        handler: impl IntoHandler<T, M, S>,
+        transform: fn(T) -> T,
+    ) -> Self
+    where
+        T: 'static,
+    {
+        let handler = handler.into_handler(&self.state);
+        self.route(method, pattern, move |request: &Request| {
+            transform(handler(request))
+        })
+    }
+
+    // wraps every matched handler of this router, whenever it was
+    // registered: call `next` to go on, or return without calling it to
+    // short-circuit. middleware added first is the outermost. it doesn't run
+    // on a miss, nor for the `maintenance`, `not_found` or
+    // `method_not_allowed` handlers, nor for a `route_exempt` route.
+    pub fn middleware(
+        mut self,
+        middleware: impl Fn(&dyn Fn(&Request) -> T, &Request) -> T + Send + Sync + 'static,
+    ) -> Self {
+        self.middleware.push(Arc::new(middleware));
+        self
+    }
+
+    // registers the routes `routes` adds to a fresh router under `config`:
+    // router.scope("/admin", |s| s.get("/users", users)) serves /admin/users,
+    // a `ScopeConfig` adds a host or middleware.
+    // nested scopes compose: prefixes are joined outer first, middleware of
+    // an outer scope wraps that of an inner one (and within one scope the
+    // first listed is the outermost), and the innermost host wins, with a
+    // route's own `route_on_host` host above any scope's.
+    pub fn scope(
+        mut self,
+        config: impl Into<ScopeConfig<T>>,
+        routes: impl FnOnce(Router<T, S>) -> Router<T, S>,
+    ) -> Self
+    where
+        T: 'static,
+    {
+        let config = config.into();
+        let host = config.host.map(|host| host.to_ascii_lowercase());
+        let scoped = routes(Router::with_state(self.state.clone()));
+        // the scoped router's own middleware runs inside the scope's
+        let middleware: Vec<_> = config.middleware.iter().chain(&scoped.middleware).collect();
+        for node in Arc::unwrap_or_clone(scoped.nodes) {
+            let pattern = join_prefix(&config.prefix, node.pattern.as_str());
+            let mut pattern = Pattern::parse(&pattern).unwrap_or_else(|e| panic!("{}", e));
+            // /admin + /users/ is /admin/users/ for `strict_slashes`, but
+            // /admin + / is /admin
+            pattern.trailing_slash =
+                node.pattern.trailing_slash && !node.pattern.as_str().is_empty();
+            let handler = middleware.iter().rev().filter(|_| !node.exempt).fold(
+                node.handler,
+                |handler, &middleware| {
+                    let middleware = middleware.clone();
+                    Arc::new(move |request: &Request, recover: &Recover<'_, T>| {
+                        middleware(&|request| handler(request, recover), request)
+                    })
+                },
+            );
+            self.nodes_mut().push(Node {
+                pattern,
+                handler,
+                host: node.host.or_else(|| host.clone()),
+                ..node
+            });
+        }
+        self
+    }
+
+    // `scope` for a router built elsewhere: app.nest("/admin", admin::router())
+    // serves its / as /admin and its /users as /admin/users. its middleware
+    // still wraps its routes, inside this router's; its options, fallback
+    // and 404 handlers are left behind. nesting a nested router prefixes
+    // again.
+    pub fn nest(self, prefix: &str, router: Router<T, S>) -> Self
+    where
+        T: 'static,
+    {
+        self.scope(prefix, |_| router)
+    }
+
+    // `scope` for a host: router.host("api.example.com", |api| api.get(..)).
+    // see `route_on_host`
+    pub fn host(self, host: &str, routes: impl FnOnce(Router<T, S>) -> Router<T, S>) -> Self
+    where
+        T: 'static,
+    {
+        let config = ScopeConfig {
+            host: Some(host.to_string()),
+            ..Default::default()
+        };
+        self.scope(config, routes)
+    }
+
+    // the routes of both routers, `other`'s after this one's, e.g. to combine
+    // routers built per feature area. everything else, options, fallback,
+    // 404 handlers and middleware, is this router's, so its middleware
+    // wraps `other`'s routes too, around `other`'s own.
+    // a route defined in both is a tie like any other repeated route: this
+    // router's copy wins, and `validate` reports the other as a duplicate.
+    pub fn merge(self, other: &Router<T, S>) -> Self
+    where
+        T: 'static,
+    {
+        self.append(other.clone())
+    }
+
+    // `merge` without cloning either router's routes
+    pub fn append(mut self, other: Router<T, S>) -> Self
+    where
+        T: 'static,
+    {
+        let middleware: Arc<[Middleware<T>]> = other.middleware.into();
+        self.nodes_mut()
+            .extend(Arc::unwrap_or_clone(other.nodes).into_iter().map(|node| {
+                if middleware.is_empty() || node.exempt {
+                    return node;
+                }
+                let middleware = middleware.clone();
+                let handler = node.handler;
+                Node {
+                    handler: Arc::new(move |request: &Request, recover: &Recover<'_, T>| {
+                        run_middleware(&middleware, &|request| handler(request, recover), request)
+                    }),
+                    ..node
+                }
+            }));
+        self
+    }
+
+    // like `route`, but a match carries `headers`, see `resolve_full`
+    pub fn route_with_headers<M>(
+        self,
+        method: Method,
+        pattern: &str,
+        handler: impl IntoHandler<T, M, S>,
+        headers: Vec<(String, String)>,
+    ) -> Self {
+        let mut router = self.route(method, pattern, handler);
+        router.nodes_mut().last_mut().unwrap().headers = headers;
+        router
+    }
+
+    // a route that keeps answering in maintenance mode, e.g. a health check
+    // for orchestrators to probe. it still has to be the best match.
+    // no middleware wraps it, a scope's or a merged router's included.
+    pub fn route_exempt<M>(
+        self,
+        method: Method,
+        pattern: &str,
+        handler: impl IntoHandler<T, M, S>,
+    ) -> Self {
+        let mut router = self.route(method, pattern, handler);
+        router.nodes_mut().last_mut().unwrap().exempt = true;
+        router
+    }
+
+    // names the route registered last, for `url_for`:
+    // .get("/users/:id/posts/:post", show_post).name("user_post")
+    pub fn name(mut self, name: &str) -> Self {
+        let node = self
+            .nodes_mut()
+            .last_mut()
+            .expect("`name` needs a route to name");
+        node.name = Some(name.to_string());
+        self
+    }
+
+    // a condition on the route registered last, checked once its pattern and
+    // method match. a route whose guard says no is skipped as if it weren't
+    // there, so the next best route answers, or else the 404:
+    // .get("/admin", admin).guard(|ctx| ctx.header("x-internal").is_some())
+    // a route with several guards needs all of them to pass.
+    pub fn guard(mut self, guard: impl Fn(&GuardCtx) -> bool + Send + Sync + 'static) -> Self {
+        let node = self
+            .nodes_mut()
+            .last_mut()
+            .expect("`guard` needs a route to guard");
+        node.guards.push(Arc::new(guard));
+        self
+    }
+
+    // the path of the route named `name`, with `args` filling its params and
+    // wildcards from the left. an argument is percent-encoded, so a `/` in it
+    // stays in its segment, except in a `*rest` or `**` which spans segments.
+    // a name given twice refers to the first route.
+    pub fn url_for(&self, name: &str, args: &[&str]) -> Result<String, UrlForError> {
+        let node = self
+            .nodes
+            .iter()
+            .find(|node| node.name.as_deref() == Some(name))
+            .ok_or_else(|| UrlForError::UnknownName(name.to_string()))?;
+        let mut url = node.pattern.fill(args)?;
+        if url.is_empty() || (self.options.strict_slashes && node.pattern.trailing_slash) {
+            url.push('/');
+        }
+        Ok(url)
+    }
+
+    pub fn get<M>(self, pattern: &str, handler: impl IntoHandler<T, M, S>) -> Self {
+        self.route(Method::GET, pattern, handler)
+    }
+    pub fn post<M>(self, pattern: &str, handler: impl IntoHandler<T, M, S>) -> Self {
+        self.route(Method::POST, pattern, handler)
+    }
+    pub fn put<M>(self, pattern: &str, handler: impl IntoHandler<T, M, S>) -> Self {
+        self.route(Method::PUT, pattern, handler)
+    }
+    pub fn delete<M>(self, pattern: &str, handler: impl IntoHandler<T, M, S>) -> Self {
+        self.route(Method::DELETE, pattern, handler)
+    }
+    pub fn patch<M>(self, pattern: &str, handler: impl IntoHandler<T, M, S>) -> Self {
+        self.route(Method::PATCH, pattern, handler)
+    }
+    pub fn head<M>(self, pattern: &str, handler: impl IntoHandler<T, M, S>) -> Self {
+        self.route(Method::HEAD, pattern, handler)
+    }
+    pub fn options<M>(self, pattern: &str, handler: impl IntoHandler<T, M, S>) -> Self {
+        self.route(Method::OPTIONS, pattern, handler)
+    }
+
+    pub fn get_fallible<M, E>(
+        self,
+        pattern: &str,
+        handler: impl IntoHandler<Result<T, E>, M, S>,
+    ) -> Self
+    where
+        T: From<String>,
+        E: Into<HandlerError> + 'static,
+    {
+        self.route_fallible(Method::GET, pattern, handler)
+    }
+    pub fn post_fallible<M, E>(
+        self,
+        pattern: &str,
+        handler: impl IntoHandler<Result<T, E>, M, S>,
+    ) -> Self
+    where
+        T: From<String>,
+        E: Into<HandlerError> + 'static,
+    {
+        self.route_fallible(Method::POST, pattern, handler)
+    }
+    pub fn put_fallible<M, E>(
+        self,
+        pattern: &str,
+        handler: impl IntoHandler<Result<T, E>, M, S>,
+    ) -> Self
+    where
+        T: From<String>,
+        E: Into<HandlerError> + 'static,
+    {
+        self.route_fallible(Method::PUT, pattern, handler)
+    }
+    pub fn delete_fallible<M, E>(
+        self,
+        pattern: &str,
+        handler: impl IntoHandler<Result<T, E>, M, S>,
+    ) -> Self
+    where
+        T: From<String>,
+        E: Into<HandlerError> + 'static,
+    {
+        self.route_fallible(Method::DELETE, pattern, handler)
+    }
+
+    // `route` with the method by name, any token goes:
+    // router.method("PROPFIND", "/files/*path", list)
+    pub fn method<M>(
+        self,
+        method: &str,
+        pattern: &str,
+        handler: impl IntoHandler<T, M, S>,
+    ) -> Self {
+        let method = Method::try_from(method).unwrap_or_else(|e| panic!("{}", e));
+        self.route(method, pattern, handler)
+    }
 }
 
-impl Router {
-    pub fn route(&self, method: Method, pattern: &str, handler: Handler) -> Self {
-        // trailing slash
-        let mut pattern = pattern.to_string();
-        if pattern.ends_with('/') {
-            pattern.pop();
+// registering routes needs `T: 'static` for the handlers, resolving doesn't
+impl<T, S: Clone> Router<T, S> {
+    // the matched handler's body. a miss is an error unless a `not_found` or
+    // `method_not_allowed` handler answers it; in maintenance mode the
+    // maintenance handler answers everything.
+    // `method` is a `Method` or its wire form, "GET", parsed once up front;
+    // an invalid one is a `RouteError::InvalidMethod`. `path` is any
+    // request-target, /foo, http://example.com/foo or `*` for OPTIONS, see
+    // `dispatch_request`.
+    pub fn resolve<M>(&self, method: M, path: &str) -> Result<T, RouteError>
+    where
+        M: TryInto<Method>,
+        RouteError: From<M::Error>,
+    {
+        self.resolve_parsed(method, None, path)
+    }
+
+    // `resolve` for callers that already hold a `Method`, skipping the parse
+    pub fn resolve_method(&self, method: Method, path: &str) -> Result<T, RouteError> {
+        self.resolve_in(Request::new(method, path), None)
+    }
+
+    // `resolve` for a full request, which handlers see as it was sent:
+    // headers, query, body and the path before normalization
+    pub fn resolve_request(&self, request: Request) -> Result<T, RouteError> {
+        self.resolve_in(request, None)
+    }
+
+    // like `resolve`, but routes registered with `route_on_host` for a
+    // matching host are preferred over routes without a host. `host` may
+    // come straight from a `Host` header, a `:port` is ignored.
+    pub fn resolve_with_host<M>(&self, method: M, host: &str, path: &str) -> Result<T, RouteError>
+    where
+        M: TryInto<Method>,
+        RouteError: From<M::Error>,
+    {
+        self.resolve_parsed(method, Some(host), path)
+    }
+
+    // resolves a raw request line such as `GET /foo HTTP/1.1`, or with any
+    // other form of request-target, see `dispatch_request`. the version
+    // is optional and extra whitespace is ignored; a malformed line is a
+    // `RouteError::BadRequest`.
+    pub fn resolve_line(&self, request_line: &str) -> Result<T, RouteError> {
+        let mut parts = request_line.split_whitespace();
+        match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(method), Some(path), version, None)
+                if request::split_target(path).is_ok()
+                    && version.is_none_or(|version| version.starts_with("HTTP/")) =>
+            {
+                self.resolve(method, path)
+            }
+            _ => Err(RouteError::BadRequest(String::from(
+                "malformed request line",
+            ))),
+        }
+    }
+
+    // like `resolve`, but `default` is called only when nothing answers
+    pub fn resolve_or<M>(&self, method: M, path: &str, default: impl FnOnce() -> T) -> T
+    where
+        M: TryInto<Method>,
+        RouteError: From<M::Error>,
+    {
+        self.resolve(method, path).unwrap_or_else(|_| default())
+    }
+
+    // like `resolve`, but returns what matched along with the body
+    pub fn resolve_full<M>(&self, method: M, path: &str) -> Result<Match<T>, RouteError>
+    where
+        M: TryInto<Method>,
+        RouteError: From<M::Error>,
+    {
+        self.dispatch(method, None, path)
+    }
+
+    fn resolve_parsed<M>(&self, method: M, host: Option<&str>, path: &str) -> Result<T, RouteError>
+    where
+        M: TryInto<Method>,
+        RouteError: From<M::Error>,
+    {
+        match method.try_into() {
+            Ok(method) => self.resolve_in(Request::new(method, path), host),
+            Err(error) => Err(self.invalid_method(error.into(), path)),
+        }
+    }
+
+    fn resolve_in(&self, mut request: Request, host: Option<&str>) -> Result<T, RouteError> {
+        let result = self.dispatch_request(&mut request, host);
+        self.respond(result, &request)
+    }
+
+    // the body for a resolve: the matched handler's, the maintenance
+    // handler's, or the 404 or 405 handler's
+    fn respond(
+        &self,
+        result: Result<Match<T>, RouteError>,
+        request: &Request,
+    ) -> Result<T, RouteError> {
+        if let (Err(RouteError::MethodNotAllowed(allowed)), Some(auto_options)) =
+            (&result, &self.auto_options)
+        {
+            if request.method == Method::OPTIONS {
+                let mut allowed: Vec<&str> = allowed.iter().map(Method::as_str).collect();
+                // a GET route answers HEAD too, see `head_fallback`
+                if let Some(get) = allowed.iter().position(|method| *method == "GET") {
+                    if self.options.head_fallback && !allowed.contains(&"HEAD") {
+                        allowed.insert(get + 1, "HEAD");
+                    }
+                }
+                return Ok(auto_options(allowed.join(", ")));
+            }
+        }
+        let handler = match &result {
+            Err(RouteError::Unavailable) => self.maintenance.as_ref(),
+            Err(RouteError::NotFound) => self.last_handler(|router| &router.not_found),
+            Err(RouteError::MethodNotAllowed(_)) => {
+                self.last_handler(|router| &router.method_not_allowed)
+            }
+            _ => None,
+        };
+        match (result, handler) {
+            (Ok(matched), _) => Ok(matched.body),
+            (Err(_), Some(handler)) => Ok(handler(request)),
+            (Err(error), None) => Err(error),
+        }
+    }
+
+    // the handler of the last router in the `or_else` chain that has one
+    fn last_handler(
+        &self,
+        handler: fn(&Router<T, S>) -> &Option<Handler<T>>,
+    ) -> Option<&Handler<T>> {
+        let fallback = self
+            .fallback
+            .as_ref()
+            .and_then(|fallback| fallback.last_handler(handler));
+        fallback.or(handler(self).as_ref())
+    }
+
+    fn dispatch<M>(&self, method: M, host: Option<&str>, path: &str) -> Result<Match<T>, RouteError>
+    where
+        M: TryInto<Method>,
+        RouteError: From<M::Error>,
+    {
+        match method.try_into() {
+            Ok(method) => self.dispatch_request(&mut Request::new(method, path), host),
+            Err(error) => Err(self.invalid_method(error.into(), path)),
+        }
+    }
+
+    // every public resolve ends up here or in `invalid_method` exactly once.
+    // the path may be any request-target: an absolute-form one is routed by
+    // its path, on its host over `host`, and handlers see the path alone.
+    fn dispatch_request(
+        &self,
+        request: &mut Request,
+        host: Option<&str>,
+    ) -> Result<Match<T>, RouteError> {
+        let start = Instant::now();
+        let result = match request::split_target(&request.path) {
+            Err(reason) => Err(RouteError::BadRequest(reason.to_string())),
+            Ok((Some(authority), path)) => {
+                let (authority, path) = (authority.to_string(), path.to_string());
+                let sent = std::mem::replace(&mut request.path, path);
+                let result = self.dispatch_inner(request, Some(&authority), &self.on_match);
+                self.report(request.method.as_str(), &sent, &result, start);
+                return result;
+            }
+            Ok((None, _)) => self.dispatch_inner(request, host, &self.on_match),
+        };
+        self.report(request.method.as_str(), &request.path, &result, start);
+        result
+    }
+
+    fn invalid_method(&self, error: RouteError, path: &str) -> RouteError {
+        let result = Err(error);
+        let method = match &result {
+            Err(RouteError::InvalidMethod(method)) => method.as_str(),
+            _ => "",
+        };
+        self.report(method, path, &result, Instant::now());
+        result.err().unwrap()
+    }
+
+    fn report(
+        &self,
+        method: &str,
+        path: &str,
+        result: &Result<Match<T>, RouteError>,
+        start: Instant,
+    ) {
+        if let Some(on_resolve) = self.options.on_resolve {
+            on_resolve(&ResolveInfo {
+                method,
+                path,
+                pattern: result.as_ref().ok().map(|matched| matched.pattern.as_str()),
+                elapsed: start.elapsed(),
+            });
+        }
+        if is_miss(result) {
+            for hook in &self.on_no_match {
+                hook(method, path);
+            }
+        }
+    }
+
+    // `on_match` is the hooks of the router resolving, not `self`'s when
+    // `self` is a fallback
+    fn dispatch_inner(
+        &self,
+        request: &mut Request,
+        host: Option<&str>,
+        on_match: &[MatchHook],
+    ) -> Result<Match<T>, RouteError> {
+        let result = self.dispatch_own(request, host, on_match);
+        let Some(fallback) = &self.fallback else {
+            return result;
+        };
+        if !is_miss(&result) {
+            return result;
+        }
+
+        match (result, fallback.dispatch_inner(request, host, on_match)) {
+            (_, Ok(mut matched)) => {
+                matched.index += self.nodes.len();
+                Ok(matched)
+            }
+            (
+                Err(RouteError::MethodNotAllowed(mut allowed)),
+                Err(RouteError::MethodNotAllowed(more)),
+            ) => {
+                for method in more {
+                    if !allowed.contains(&method) {
+                        allowed.push(method);
+                    }
+                }
+                Err(RouteError::MethodNotAllowed(allowed))
+            }
+            (Err(own @ RouteError::MethodNotAllowed(_)), Err(_)) => Err(own),
+            (_, result) => result,
+        }
+    }
+
+    // `dispatch_inner` without the fallback
+    fn dispatch_own(
+        &self,
+        request: &mut Request,
+        host: Option<&str>,
+        on_match: &[MatchHook],
+    ) -> Result<Match<T>, RouteError> {
+        let method = &request.method.clone();
+        if self.options.maintenance && self.maintenance.is_some() {
+            // only exempt routes still answer, see `route_exempt`
+            let path = normalize(&request.path, &self.options);
+            return match self.find(method, request, host, &path) {
+                Some((index, node, params))
+                    if node.exempt && pattern::check_path(&path).is_ok() =>
+                {
+                    Ok(self.run(index, node, request, params, &path, on_match))
+                }
+                _ => Err(RouteError::Unavailable),
+            };
+        }
+        if self.options.unknown_method == UnknownMethod::NotImplemented
+            && !self.nodes.iter().any(|node| self.serves(node, method))
+        {
+            return Err(RouteError::NotImplemented(method.clone()));
+        }
+        if request.path == "*" {
+            return self.asterisk(method);
+        }
+        pattern::check_path(&request.path)
+            .map_err(|reason| RouteError::BadRequest(reason.to_string()))?;
+        let path = normalize(&request.path, &self.options);
+
+        let Some((index, node, params)) = self.find(method, request, host, &path) else {
+            let allowed = self.allowed_methods(request, host, &path);
+            return Err(match allowed.is_empty() {
+                true => RouteError::NotFound,
+                false => RouteError::MethodNotAllowed(allowed),
+            });
+        };
+        Ok(self.run(index, node, request, params, &path, on_match))
+    }
+
+    // runs the matched `node` and tells the hooks, `path` is normalized
+    fn run(
+        &self,
+        index: usize,
+        node: &Node<T>,
+        request: &mut Request,
+        params: Params,
+        path: &str,
+        on_match: &[MatchHook],
+    ) -> Match<T> {
+        let start = Instant::now();
+        let matched = node.to_match(
+            index,
+            request,
+            params,
+            &self.middleware,
+            self.error_handler.as_ref(),
+        );
+        let info = MatchInfo {
+            method: request.method.as_str(),
+            path,
+            pattern: node.pattern.as_str(),
+            elapsed: start.elapsed(),
+        };
+        for hook in on_match {
+            hook(&info);
+        }
+        matched
+    }
+
+    // `OPTIONS *` asks about the server as a whole: a 405 listing every
+    // method a route is registered with, for `auto_options` or a
+    // `method_not_allowed` handler to answer, or a 404 without routes
+    fn asterisk(&self, method: &Method) -> Result<Match<T>, RouteError> {
+        if method != &Method::OPTIONS {
+            return Err(RouteError::BadRequest(String::from(
+                "`*` is only a target for OPTIONS",
+            )));
+        }
+        let mut methods: Vec<Method> = vec![];
+        for node in self.nodes.iter() {
+            if let MethodMatcher::Only(method) = &node.method {
+                if !methods.contains(method) {
+                    methods.push(method.clone());
+                }
+            }
+        }
+        match methods.is_empty() {
+            true => Err(RouteError::NotFound),
+            false => Err(RouteError::MethodNotAllowed(methods)),
+        }
+    }
+
+    // the most specific matching route wins: an exact host, then a wildcard
+    // host, then no host, and after that see `specificity`. then a route for
+    // the method itself beats a GET route answering a HEAD, which beats an
+    // `any` route.
+    // equally specific routes are tried in registration order: `min_by`
+    // keeps the first of equal elements, so the first registered wins, every
+    // time. `conflicts` lists each such tie.
+    fn find<'a>(
+        &'a self,
+        method: &'a Method,
+        request: &Request,
+        host: Option<&str>,
+        path: &'a str,
+    ) -> Option<(usize, &'a Node<T>, Params)> {
+        let Some(cache) = self.route_cache() else {
+            return self.find_uncached(method, request, host, path);
+        };
+        // a method has no NUL, but in maintenance mode the path isn't
+        // checked yet and may, so two keys can collide. a hit is matched
+        // against the path and host again by `match_node`, so a collision
+        // can't pick a route that doesn't match.
+        let key = format!("{}\0{}\0{}", method.as_str(), path, host.unwrap_or(""));
+        if let Some(index) = cache.get(&key) {
+            let node = &self.nodes[index];
+            let host = host.map(|host| strip_port(host).to_ascii_lowercase());
+            if let Some(params) = self.match_node(node, host.as_deref(), path) {
+                return Some((index, node, params));
+            }
+        }
+        let found = self.find_uncached(method, request, host, path);
+        if let Some((index, ..)) = found {
+            cache.insert(&key, index);
+        }
+        found
+    }
+
+    fn route_cache(&self) -> Option<&RouteCache> {
+        if self.cache_capacity == 0 {
+            return None;
+        }
+        self.cache
+            .get_or_init(|| {
+                let guarded = self.nodes.iter().any(|node| !node.guards.is_empty());
+                (!guarded).then(|| RouteCache::new(self.cache_capacity))
+            })
+            .as_ref()
+    }
+
+    fn find_uncached<'a>(
+        &'a self,
+        method: &'a Method,
+        request: &Request,
+        host: Option<&str>,
+        path: &'a str,
+    ) -> Option<(usize, &'a Node<T>, Params)> {
+        self.matching(Some(method), host, path)
+            .filter(|(_, node, params)| node.passes(request, params))
+            .min_by(|(_, a, _), (_, b, _)| {
+                (
+                    host_rank(&a.host),
+                    &a.pattern.specificity,
+                    method_rank(&a.method, method),
+                )
+                    .cmp(&(
+                        host_rank(&b.host),
+                        &b.pattern.specificity,
+                        method_rank(&b.method, method),
+                    ))
+            })
+    }
+
+    // the methods of every route matching `path`, in registration order
+    fn allowed_methods(&self, request: &Request, host: Option<&str>, path: &str) -> Vec<Method> {
+        let mut methods: Vec<Method> = vec![];
+        // an `any` route would have matched
+        for (_, node, params) in self.matching(None, host, path) {
+            if !node.passes(request, &params) {
+                continue;
+            }
+            if let MethodMatcher::Only(method) = &node.method {
+                if !methods.contains(method) {
+                    methods.push(method.clone());
+                }
+            }
+        }
+        methods
+    }
+
+    // routes whose host and pattern fit, for `method` or any method
+    fn matching<'a>(
+        &'a self,
+        method: Option<&'a Method>,
+        host: Option<&str>,
+        path: &'a str,
+    ) -> impl Iterator<Item = (usize, &'a Node<T>, Params)> + 'a {
+        let host = host.map(|host| strip_port(host).to_ascii_lowercase());
+        let index = self.index.get_or_init(|| {
+            RouteIndex::new(self.nodes.iter().map(|node| &node.pattern), &self.options)
+        });
+        index
+            .candidates(path, &self.options)
+            .into_iter()
+            .map(|index| (index, &self.nodes[index]))
+            .filter(move |(_, node)| method.is_none_or(|method| self.serves(node, method)))
+            .filter_map(move |(index, node)| {
+                let params = self.match_node(node, host.as_deref(), path)?;
+                Some((index, node, params))
+            })
+    }
+
+    // `node`'s params for `host`, without a port and lowercase, and `path`
+    fn match_node(&self, node: &Node<T>, host: Option<&str>, path: &str) -> Option<Params> {
+        let mut params = node.pattern.match_path(path, &self.options)?;
+        if let Some(node_host) = &node.host {
+            if let Some(subdomain) = match_host(node_host, host?)? {
+                params.push("subdomain", subdomain);
+            }
+        }
+        Some(params)
+    }
+
+    // whether `node` answers `method`, see `head_fallback`
+    fn serves(&self, node: &Node<T>, method: &Method) -> bool {
+        node.method.matches(method)
+            || (self.options.head_fallback && method == &Method::HEAD && node.method == Method::GET)
+    }
+
+    // pairs of routes that can never be told apart: same method, same
+    // specificity, and overlapping segments. only the first of each pair is
+    // ever matched.
+    // /users/:id and /users/:name, /files/* and /files/:file, /a and /a
+    pub fn conflicts(&self) -> Vec<Conflict> {
+        let mut conflicts = vec![];
+        for (first, a) in self.nodes.iter().enumerate() {
+            for (second, b) in self.nodes.iter().enumerate().skip(first + 1) {
+                if self.shadows(a, b) {
+                    conflicts.push(Conflict::new(first, a, second, b));
+                }
+            }
+        }
+        conflicts
+    }
+
+    // a guarded route leaves the rest to `b` whenever its guard says no
+    fn shadows(&self, a: &Node<T>, b: &Node<T>) -> bool {
+        a.guards.is_empty()
+            && a.method == b.method
+            && a.host == b.host
+            && a.pattern.specificity == b.pattern.specificity
+            && a.pattern.overlaps(&b.pattern)
+            && (!self.options.strict_slashes
+                || a.pattern.trailing_slash == b.pattern.trailing_slash)
+    }
+
+    // every static check at once, to run at startup. each of `conflicts`
+    // becomes one warning, see `RouterWarning`. malformed patterns never get
+    // this far, `route` already rejects them.
+    pub fn validate(&self) -> Result<(), Vec<RouterWarning>> {
+        let warnings: Vec<RouterWarning> = self
+            .conflicts()
+            .into_iter()
+            .map(|conflict| {
+                let first = &self.nodes[conflict.first].pattern;
+                let second = &self.nodes[conflict.second].pattern;
+                if first.as_str() == second.as_str() {
+                    RouterWarning::Duplicate(conflict)
+                } else if first.covers(second) {
+                    RouterWarning::Unreachable(conflict)
+                } else {
+                    RouterWarning::Conflict(conflict)
+                }
+            })
+            .collect();
+        match warnings.is_empty() {
+            true => Ok(()),
+            false => Err(warnings),
+        }
+    }
+
+    // matches a leading run of segments against a route (a trailing `**` is
+    // ignored) and returns the raw, unconsumed rest of the path.
+    // /api/** with /api/users/1 -> /users/1
+    pub fn match_prefix<'a>(
+        &'a self,
+        method: Method,
+        path: &'a str,
+    ) -> Option<(&'a Node<T>, &'a str)> {
+        self.nodes
+            .iter()
+            .filter(|node| node.method.matches(&method) && node.host.is_none())
+            .filter_map(|node| {
+                node.pattern
+                    .match_prefix(path, &self.options)
+                    .map(|rest| (node, rest))
+            })
+            .min_by(|(a, _), (b, _)| a.pattern.specificity.cmp(&b.pattern.specificity))
+    }
+
+    // every route, in registration order, so a `Match::index` finds the
+    // route that matched: router.routes().nth(matched.index). which route
+    // wins a request goes by specificity instead, see `find`.
+    pub fn routes(&self) -> impl Iterator<Item = &Node<T>> {
+        self.nodes.iter()
+    }
+
+    // the number of routes, fallbacks not included
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    // every route registered for `method`, `any` routes included, in
+    // registration order
+    pub fn routes_for_method(&self, method: Method) -> impl Iterator<Item = &Node<T>> {
+        self.nodes
+            .iter()
+            .filter(move |node| node.method.matches(&method))
+    }
+
+    // converts every handler's output with `f`, e.g. to combine routers built
+    // for different output types
+    pub fn map_handlers<U>(self, f: fn(T) -> U) -> Router<U, S>
+    where
+        T: 'static,
+        U: 'static,
+    {
+        // middleware and the error handler only know `T`, so they're applied
+        // before converting
+        let middleware: Arc<[Middleware<T>]> = self.middleware.into();
+        let nodes = Arc::unwrap_or_clone(self.nodes)
+            .into_iter()
+            .map(|node| {
+                let handler = node.handler;
+                let middleware = match node.exempt {
+                    true => Arc::default(),
+                    false => middleware.clone(),
+                };
+                let error_handler = self.error_handler.clone();
+                Node {
+                    method: node.method,
+                    pattern: node.pattern,
+                    handler: Arc::new(move |request: &Request, recover: &Recover<'_, U>| {
+                        let recover = Recover {
+                            handler: error_handler.as_ref(),
+                            error: recover.error,
+                        };
+                        f(run_middleware(
+                            &middleware,
+                            &|request| handler(request, &recover),
+                            request,
+                        ))
+                    }),
+                    host: node.host,
+                    headers: node.headers,
+                    exempt: node.exempt,
+                    name: node.name,
+                    guards: node.guards,
+                }
+            })
+            .collect::<Vec<_>>()
+            .into();
+        let map = |handler: Handler<T>| -> Handler<U> {
+            Arc::new(move |request: &Request| f(handler(request)))
+        };
+        let fallback = self
+            .fallback
+            .map(|fallback| Arc::new((*fallback).clone().map_handlers(f)));
+        Router {
+            nodes,
+            state: self.state,
+            options: self.options,
+            maintenance: self.maintenance.map(map),
+            fallback,
+            not_found: self.not_found.map(map),
+            method_not_allowed: self.method_not_allowed.map(map),
+            auto_options: self.auto_options.map(
+                |auto_options| -> Arc<dyn Fn(String) -> U + Send + Sync> {
+                    Arc::new(move |allowed| f(auto_options(allowed)))
+                },
+            ),
+            middleware: vec![],
+            error_handler: self
+                .error_handler
+                .map(|handler| -> ErrorHandler<U> { Arc::new(move |error| f(handler(error))) }),
+            on_match: self.on_match,
+            on_no_match: self.on_no_match,
+            index: Arc::default(),
+            cache_capacity: self.cache_capacity,
+            cache: Arc::default(),
+        }
+    }
+}
+
+impl<T: From<String> + 'static, S> Router<T, S> {
+    // answers an OPTIONS request no OPTIONS route matches with the methods
+    // of every route matching the path, in registration order: "GET, POST",
+    // and HEAD after GET while `head_fallback` is on.
+    // a path no route matches is still a miss, and `resolve_full` still
+    // reports the `RouteError::MethodNotAllowed`. off by default.
+    pub fn auto_options(mut self, on: bool) -> Self {
+        self.auto_options = match on {
+            true => Some(Arc::new(T::from)),
+            false => None,
+        };
+        self
+    }
+}
+
+// a body produced chunk by chunk, for large reports or server-sent events
+#[cfg(feature = "stream")]
+pub type Stream = Box<dyn Iterator<Item = String> + Send>;
+
+// streaming routers have handlers returning a `Stream`:
+// router.get("/report", || -> Stream { Box::new(rows().map(to_csv)) })
+#[cfg(feature = "stream")]
+impl<S: Clone> Router<Stream, S> {
+    // the matched handler's chunks, unread, so an adapter can write each one
+    // as it's produced. a miss is a single `no match routes` chunk.
+    pub fn resolve_stream(&self, method: &str, path: &str) -> Stream {
+        self.resolve_or(method, path, || {
+            Box::new(std::iter::once(String::from("no match routes")))
+        })
+    }
+}
+
+// binary bodies (images, PDFs, ...) use a `Router<Vec<u8>>`, with handlers
+// returning `Vec<u8>`. a string router converts with
+// `map_handlers(String::into_bytes)`; the two never mix in one router.
+impl<S: Clone> Router<Vec<u8>, S> {
+    // like `resolve`, misses answer with the same body as bytes
+    pub fn resolve_bytes(&self, method: &str, path: &str) -> Vec<u8> {
+        self.resolve_or(method, path, || b"no match routes".to_vec())
+    }
+}
+
+#[derive(new)]
+pub struct Node<T = String> {
+    method: MethodMatcher,
+    pattern: Pattern,
+    handler: RouteHandler<T>,
+    host: Option<String>,
+    #[new(default)]
+    headers: Vec<(String, String)>,
+    #[new(default)]
+    exempt: bool,
+    #[new(default)]
+    name: Option<String>,
+    #[new(default)]
+    guards: Vec<Guard>,
+}
+
+impl<T> Clone for Node<T> {
+    fn clone(&self) -> Self {
+        Self {
+            method: self.method.clone(),
+            pattern: self.pattern.clone(),
+            handler: self.handler.clone(),
+            host: self.host.clone(),
+            headers: self.headers.clone(),
+            exempt: self.exempt,
+            name: self.name.clone(),
+            guards: self.guards.clone(),
+        }
+    }
+}
+
+// a successful `Router::resolve_full`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match<T = String> {
+    // position of the matched route in registration order. a compact key for
+    // per-route metrics, stable as long as no route is removed or reordered.
+    pub index: usize,
+    pub method: Method,
+    // as registered, after trailing slash trimming
+    pub pattern: String,
+    // named params and, positionally, wildcards
+    pub params: Params,
+    // from `Router::route_with_headers`
+    pub headers: Vec<(String, String)>,
+    pub body: T,
+    // what a `Router::route_fallible` handler failed with, rendered, and
+    // `body` is then the error handler's
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RouteError {
+    NotFound,
+    // the path matches, but only routes for these other methods
+    MethodNotAllowed(Vec<Method>),
+    InvalidMethod(String),
+    // no route uses this method, see `Router::unknown_method`
+    NotImplemented(Method),
+    // the path can't be safely decoded, so it isn't matched at all:
+    // a malformed `%` escape, an escape that decodes to invalid UTF-8, or a
+    // control character (NUL, newline, ...) either raw or percent-encoded
+    BadRequest(String),
+    // in maintenance mode, see `Router::maintenance`
+    Unavailable,
+}
+
+impl fmt::Display for RouteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RouteError::NotFound => write!(f, "no match routes"),
+            RouteError::MethodNotAllowed(allowed) => {
+                let allowed: Vec<&str> = allowed.iter().map(Method::as_str).collect();
+                write!(f, "method not allowed, allowed: {}", allowed.join(", "))
+            }
+            RouteError::InvalidMethod(method) => write!(f, "invalid method `{}`", method),
+            RouteError::NotImplemented(method) => {
+                write!(f, "method `{}` not implemented", method.as_str())
+            }
+            RouteError::BadRequest(reason) => write!(f, "bad request: {}", reason),
+            RouteError::Unavailable => write!(f, "service unavailable"),
+        }
+    }
+}
+
+impl RouteError {
+    // the matching HTTP status code
+    pub fn status(&self) -> u16 {
+        match self {
+            RouteError::NotFound => 404,
+            RouteError::MethodNotAllowed(_) => 405,
+            RouteError::InvalidMethod(_) | RouteError::BadRequest(_) => 400,
+            RouteError::NotImplemented(_) => 501,
+            RouteError::Unavailable => 503,
+        }
+    }
+}
+
+impl Error for RouteError {}
+
+// for `resolve(Method::GET, ..)`, a `Method` always converts
+impl From<Infallible> for RouteError {
+    fn from(never: Infallible) -> Self {
+        match never {}
+    }
+}
+
+// values captured from the matched route, in pattern order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Params {
+    entries: Vec<(String, String)>,
+    // what each unnamed `*`, `*.json` and `**` matched, see `wildcard`
+    wildcards: Vec<String>,
+}
+
+impl Params {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    // the `index`th value in pattern order, /posts/:post/comments/:comment
+    // has the comment at 1
+    pub fn get_index(&self, index: usize) -> Option<&str> {
+        self.entries.get(index).map(|(_, value)| value.as_str())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+
+    // the `index`th unnamed wildcard's value, `*` decoded and `**` raw like
+    // params: /a/*/b/** with /a/x/b/c/d has x at 0 and c/d at 1.
+    // a `**` that matched nothing is "".
+    pub fn wildcard(&self, index: usize) -> Option<&str> {
+        self.wildcards.get(index).map(String::as_str)
+    }
+
+    pub fn wildcards(&self) -> impl Iterator<Item = &str> {
+        self.wildcards.iter().map(String::as_str)
+    }
+
+    // the named params, wildcards not included
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // typed, positional extraction:
+    // let (id, slug): (u64, String) = params.extract()?;
+    pub fn extract<T: FromParams>(&self) -> Result<T, ParamError> {
+        T::from_params(self)
+    }
+
+    // a param parsed, for a 400 on garbage:
+    // let id: u32 = params.get_as("id")?;
+    pub fn get_as<T: FromParam>(&self, name: &str) -> Result<T, ParamError> {
+        let value = self.get(name).ok_or_else(|| ParamError::Unknown {
+            name: name.to_string(),
+        })?;
+        parse_param(name, value)
+    }
+
+    fn parse_at<T: FromParam>(&self, index: usize) -> Result<T, ParamError> {
+        let (name, value) = self
+            .entries
+            .get(index)
+            .ok_or(ParamError::Missing { index })?;
+        parse_param(name, value)
+    }
+
+    fn push(&mut self, name: &str, value: &str) {
+        self.entries.push((name.to_string(), value.to_string()));
+    }
+}
+
+fn parse_param<T: FromParam>(name: &str, value: &str) -> Result<T, ParamError> {
+    T::from_param(value).map_err(|message| ParamError::Invalid {
+        name: name.to_string(),
+        value: value.to_string(),
+        message,
+    })
+}
+
+// a single param's type, see `Params::get_as`. every `FromStr` type is one;
+// a type without `FromStr` implements this instead, the error message ends
+// up in `ParamError::Invalid`.
+pub trait FromParam: Sized {
+    fn from_param(value: &str) -> Result<Self, String>;
+}
+
+impl<T> FromParam for T
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    fn from_param(value: &str) -> Result<Self, String> {
+        value.parse().map_err(|e: T::Err| e.to_string())
+    }
+}
+
+// types `Params::extract` can produce. implemented for tuples of up to four
+// `FromParam` types, filled from the params in pattern order.
+pub trait FromParams: Sized {
+    fn from_params(params: &Params) -> Result<Self, ParamError>;
+}
+
+macro_rules! impl_from_params {
+    ($($index:tt $ty:ident),+) => {
+        impl<$($ty),+> FromParams for ($($ty,)+)
+        where
+            $($ty: FromParam),+
+        {
+            fn from_params(params: &Params) -> Result<Self, ParamError> {
+                Ok(($(params.parse_at::<$ty>($index)?,)+))
+            }
+        }
+    };
+}
+
+impl_from_params!(0 A);
+impl_from_params!(0 A, 1 B);
+impl_from_params!(0 A, 1 B, 2 C);
+impl_from_params!(0 A, 1 B, 2 C, 3 D);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParamError {
+    // fewer params than requested
+    Missing {
+        index: usize,
+    },
+    // no param of that name
+    Unknown {
+        name: String,
+    },
+    // the param didn't parse as the requested type
+    Invalid {
+        name: String,
+        value: String,
+        message: String,
+    },
+}
+
+impl fmt::Display for ParamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParamError::Missing { index } => write!(f, "missing param #{}", index),
+            ParamError::Unknown { name } => write!(f, "no param `{}`", name),
+            ParamError::Invalid {
+                name,
+                value,
+                message,
+            } => write!(
+                f,
+                "invalid value `{}` for param `{}`: {}",
+                value, name, message
+            ),
+        }
+    }
+}
+
+impl Error for ParamError {}
+
+// from `Router::url_for`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UrlForError {
+    UnknownName(String),
+    // optional trailing params may be left out
+    WrongArgumentCount { expected: usize, got: usize },
+    // would leave an empty segment, /users//posts
+    EmptyArgument { index: usize },
+}
+
+impl fmt::Display for UrlForError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UrlForError::UnknownName(name) => write!(f, "no route named `{}`", name),
+            UrlForError::WrongArgumentCount { expected, got } => {
+                write!(f, "expected {} arguments, got {}", expected, got)
+            }
+            UrlForError::EmptyArgument { index } => write!(f, "argument #{} is empty", index),
+        }
+    }
+}
+
+impl Error for UrlForError {}
+
+// a route that shadows another one, see `Router::conflicts`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub method: MethodMatcher,
+    pub first: usize,
+    pub second: usize,
+    pub first_pattern: String,
+    pub second_pattern: String,
+}
+
+impl Conflict {
+    fn new<T>(first: usize, a: &Node<T>, second: usize, b: &Node<T>) -> Self {
+        Conflict {
+            method: a.method.clone(),
+            first,
+            second,
+            first_pattern: a.pattern.to_string(),
+            second_pattern: b.pattern.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} `{}` conflicts with #{} `{}`",
+            self.method.as_str(),
+            self.second_pattern,
+            self.first,
+            self.first_pattern
+        )
+    }
+}
+
+impl Error for Conflict {}
+
+// from `Router::try_route`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TryRouteError {
+    Pattern(PatternError),
+    Conflict(Conflict),
+}
+
+impl fmt::Display for TryRouteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryRouteError::Pattern(error) => error.fmt(f),
+            TryRouteError::Conflict(conflict) => conflict.fmt(f),
+        }
+    }
+}
+
+impl Error for TryRouteError {}
+
+impl From<PatternError> for TryRouteError {
+    fn from(error: PatternError) -> Self {
+        TryRouteError::Pattern(error)
+    }
+}
+
+impl From<Conflict> for TryRouteError {
+    fn from(conflict: Conflict) -> Self {
+        TryRouteError::Conflict(conflict)
+    }
+}
+
+// from `Router::validate`, the `Conflict` says which routes to fix
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RouterWarning {
+    // the same pattern registered twice, the second is dead
+    Duplicate(Conflict),
+    // the second can never match: /users/:id then /users/:name
+    Unreachable(Conflict),
+    // the second only gets what the first doesn't match:
+    // /a/:id:int then /a/:id:enum(1|x)
+    Conflict(Conflict),
+}
+
+impl fmt::Display for RouterWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (kind, conflict) = match self {
+            RouterWarning::Duplicate(conflict) => ("duplicate route", conflict),
+            RouterWarning::Unreachable(conflict) => ("unreachable route", conflict),
+            RouterWarning::Conflict(conflict) => ("conflicting route", conflict),
+        };
+        write!(
+            f,
+            "{} #{} {} `{}`, shadowed by #{} `{}`",
+            kind,
+            conflict.second,
+            conflict.method.as_str(),
+            conflict.second_pattern,
+            conflict.first,
+            conflict.first_pattern
+        )
+    }
+}
+
+// a `Host` header without its port:
+// example.com:8080 -> example.com, [::1]:80 -> [::1]
+fn strip_port(host: &str) -> &str {
+    match host.rsplit_once(':') {
+        Some((host, port)) if port.bytes().all(|b| b.is_ascii_digit()) => host,
+        _ => host,
+    }
+}
+
+// one pass, one allocation:
+// /foo////bar/ -> /foo/bar, unless `preserve_double_slashes` or
+// `strict_slashes` keep the slashes.
+// dot segments are resolved as in RFC 3986 (remove_dot_segments), encoded
+// or not: /a/./b -> /a/b, /a/b/../c -> /a/c, /a/%2E%2E -> /. a `..` can't
+// climb above the root, /../admin is /admin, so no route ever sees one.
+fn normalize(path: &str, options: &Options) -> String {
+    let mut normalized = String::with_capacity(path.len());
+    let mut segments = path.split('/').peekable();
+    // the leading "" of an absolute path, the root
+    if let Some(first) = segments.next_if(|segment| segment.is_empty()) {
+        normalized.push_str(first);
+    }
+    while let Some(segment) = segments.next() {
+        let last = segments.peek().is_none();
+        match segment {
+            "" if !options.preserve_double_slashes && !last => continue,
+            _ if is_dot(segment, ".") => {}
+            _ if is_dot(segment, "..") => {
+                if let Some(slash) = normalized.rfind('/') {
+                    normalized.truncate(slash);
+                }
+            }
+            _ => {
+                normalized.push('/');
+                normalized.push_str(segment);
+                continue;
+            }
+        }
+        // a dot segment at the end leaves a directory: /a/b/.. -> /a/
+        if last {
+            normalized.push('/');
+        }
+    }
+    if !path.starts_with('/') {
+        normalized = normalized.trim_start_matches('/').to_string();
+    }
+    if !options.strict_slashes && normalized.ends_with('/') {
+        normalized.pop();
+    }
+    normalized
+}
+
+// `.` and `..`, with any of the dots percent-encoded
+fn is_dot(segment: &str, dots: &str) -> bool {
+    let mut rest = segment;
+    for _ in 0..dots.len() {
+        rest = match rest.strip_prefix('.') {
+            Some(rest) => rest,
+            None if rest
+                .get(..3)
+                .is_some_and(|dot| dot.eq_ignore_ascii_case("%2e")) =>
+            {
+                &rest[3..]
+            }
+            None => return false,
+        };
+    }
+    rest.is_empty()
+}
+
+fn normalize_path(path: &str) -> String {
+    normalize(path, &Options::default())
+}
+
+// `host` is already lowercase. returns the label matched by a leading `*`.
+fn match_host<'a>(node_host: &str, host: &'a str) -> Option<Option<&'a str>> {
+    match node_host.strip_prefix("*.") {
+        Some(domain) => {
+            let subdomain = host.strip_suffix(domain)?.strip_suffix('.')?;
+            if subdomain.is_empty() || subdomain.contains('.') {
+                return None;
+            }
+            Some(Some(subdomain))
+        }
+        None => (node_host == host).then_some(None),
+    }
+}
+
+// exact host, then wildcard host, then no host
+fn host_rank(host: &Option<String>) -> u8 {
+    match host {
+        Some(host) if host.starts_with("*.") => 1,
+        Some(_) => 0,
+        None => 2,
+    }
+}
+
+impl<T> fmt::Debug for Node<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Node")
+            .field("method", &format_args!("{}", self.method.as_str()))
+            .field("pattern", &self.pattern)
+            .finish_non_exhaustive()
+    }
+}
+
+// GET /users/:id, or GET api.example.com/users/:id with a host
+impl<T> fmt::Display for Node<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}{}",
+            self.method.as_str(),
+            self.host.as_deref().unwrap_or(""),
+            match self.pattern.as_str() {
+                "" => "/",
+                pattern => pattern,
+            }
+        )
+    }
+}
+
+impl<T> Node<T> {
+    pub fn method(&self) -> &MethodMatcher {
+        &self.method
+    }
+
+    pub fn pattern(&self) -> &str {
+        self.pattern.as_str()
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    // from `route_on_host` or a scope, lowercase
+    pub fn host(&self) -> Option<&str> {
+        self.host.as_deref()
+    }
+
+    // whether every guard lets `request` through, see `Router::guard`
+    fn passes(&self, request: &Request, params: &Params) -> bool {
+        let ctx = GuardCtx { request, params };
+        self.guards.iter().all(|guard| guard(&ctx))
+    }
+
+    fn to_match(
+        &self,
+        index: usize,
+        request: &mut Request,
+        params: Params,
+        middleware: &[Middleware<T>],
+        error_handler: Option<&ErrorHandler<T>>,
+    ) -> Match<T> {
+        request.params = params.clone();
+        let error = RefCell::new(None);
+        let recover = Recover {
+            handler: error_handler,
+            error: &error,
+        };
+        let middleware = if self.exempt { &[] } else { middleware };
+        Match {
+            index,
+            body: run_middleware(
+                middleware,
+                &|request| (self.handler)(request, &recover),
+                request,
+            ),
+            method: match &self.method {
+                MethodMatcher::Only(method) => method.clone(),
+                MethodMatcher::Any => request.method.clone(),
+            },
+            pattern: self.pattern.to_string(),
+            params,
+            headers: self.headers.clone(),
+            error: error.into_inner(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Method {
+    GET,
+    POST,
+    PUT,
+    DELETE,
+    PATCH,
+    HEAD,
+    OPTIONS,
+    CONNECT,
+    TRACE,
+    // any other valid method token, PROPFIND, MKCOL, REPORT, ... stored
+    // uppercase when parsed
+    Custom(String),
+}
+
+// by name, ignoring case like `TryFrom<&str>` does, so a hand-built
+// Custom("propfind") is PROPFIND
+impl PartialEq for Method {
+    fn eq(&self, other: &Method) -> bool {
+        self.as_str().eq_ignore_ascii_case(other.as_str())
+    }
+}
+
+impl Eq for Method {}
+
+// accepted spellings of each method, compared case-insensitively.
+// add a row here to accept another alias.
+const METHOD_ALIASES: &[(&str, Method)] = &[
+    ("GET", Method::GET),
+    ("POST", Method::POST),
+    ("PUT", Method::PUT),
+    ("DELETE", Method::DELETE),
+    ("PATCH", Method::PATCH),
+    ("HEAD", Method::HEAD),
+    ("OPTIONS", Method::OPTIONS),
+    ("CONNECT", Method::CONNECT),
+    ("TRACE", Method::TRACE),
+];
+
+impl Method {
+    // canonical (uppercase) wire form
+    pub fn as_str(&self) -> &str {
+        match self {
+            Method::GET => "GET",
+            Method::POST => "POST",
+            Method::PUT => "PUT",
+            Method::DELETE => "DELETE",
+            Method::PATCH => "PATCH",
+            Method::HEAD => "HEAD",
+            Method::OPTIONS => "OPTIONS",
+            Method::CONNECT => "CONNECT",
+            Method::TRACE => "TRACE",
+            Method::Custom(token) => token,
+        }
+    }
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// the methods a route answers, see `Router::any`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MethodMatcher {
+    Only(Method),
+    Any,
+}
+
+impl MethodMatcher {
+    pub fn matches(&self, method: &Method) -> bool {
+        match self {
+            MethodMatcher::Only(only) => only == method,
+            MethodMatcher::Any => true,
+        }
+    }
+
+    // the method's wire form, or `ANY`
+    pub fn as_str(&self) -> &str {
+        match self {
+            MethodMatcher::Only(method) => method.as_str(),
+            MethodMatcher::Any => "ANY",
+        }
+    }
+}
+
+impl From<Method> for MethodMatcher {
+    fn from(method: Method) -> Self {
+        MethodMatcher::Only(method)
+    }
+}
+
+// so a route's matcher compares with a plain method: node.method() == &Method::GET
+impl PartialEq<Method> for MethodMatcher {
+    fn eq(&self, method: &Method) -> bool {
+        matches!(self, MethodMatcher::Only(only) if only == method)
+    }
+}
+
+impl PartialEq<MethodMatcher> for Method {
+    fn eq(&self, matcher: &MethodMatcher) -> bool {
+        matcher == self
+    }
+}
+
+// 0 for a route of `method` itself, 1 for a GET answering a HEAD, 2 for `any`
+fn method_rank(matcher: &MethodMatcher, method: &Method) -> u8 {
+    match matcher {
+        MethodMatcher::Only(only) if only == method => 0,
+        MethodMatcher::Only(_) => 1,
+        MethodMatcher::Any => 2,
+    }
+}
+
+// tchar from RFC 7230
+fn is_token_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c)
+}
+
+impl TryFrom<&str> for Method {
+    type Error = RouteError;
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        if let Some((_, method)) = METHOD_ALIASES
+            .iter()
+            .find(|(alias, _)| alias.eq_ignore_ascii_case(value))
+        {
+            return Ok(method.clone());
+        }
+
+        if value.is_empty() || !value.chars().all(is_token_char) {
+            return Err(RouteError::InvalidMethod(value.to_string()));
+        }
+        Ok(Method::Custom(value.to_ascii_uppercase()))
+    }
+}
+
+impl TryFrom<String> for Method {
+    type Error = RouteError;
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Method::try_from(value.as_str())
+    }
+}
+
+impl TryFrom<&String> for Method {
+    type Error = RouteError;
+    fn try_from(value: &String) -> Result<Self, Self::Error> {
+        Method::try_from(value.as_str())
+    }
+}
+
+impl FromStr for Method {
+    type Err = RouteError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Method::try_from(s)
+    }
+}
+
+// wraps a matched handler: call `next` to run it (and any inner
+// middleware), or return without calling it to short-circuit
+pub type Middleware<T = String> = Arc<dyn Fn(&dyn Fn(&Request) -> T, &Request) -> T + Send + Sync>;
+
+// `handler` inside `middleware`, the first outermost
+fn run_middleware<T>(
+    middleware: &[Middleware<T>],
+    handler: &dyn Fn(&Request) -> T,
+    request: &Request,
+) -> T {
+    match middleware.split_first() {
+        Some((first, rest)) => first(&|request| run_middleware(rest, handler, request), request),
+        None => handler(request),
+    }
+}
+
+// shared settings for a block of routes, see `Router::scope`
+pub struct ScopeConfig<T = String> {
+    // prepended to every pattern, `/admin`
+    pub prefix: String,
+    // for routes without a host of their own, see `Router::route_on_host`
+    pub host: Option<String>,
+    pub middleware: Vec<Middleware<T>>,
+}
+
+impl<T> Default for ScopeConfig<T> {
+    fn default() -> Self {
+        Self {
+            prefix: String::new(),
+            host: None,
+            middleware: vec![],
+        }
+    }
+}
+
+// a scope with only a prefix: router.scope("/api", |s| s.get("/users", users))
+impl<T> From<&str> for ScopeConfig<T> {
+    fn from(prefix: &str) -> Self {
+        ScopeConfig {
+            prefix: prefix.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+// /api + /users -> /api/users, /api/ + / -> /api
+fn join_prefix(prefix: &str, pattern: &str) -> String {
+    let parts: Vec<&str> = [prefix, pattern]
+        .iter()
+        .map(|part| part.trim_matches('/'))
+        .filter(|part| !part.is_empty())
+        .collect();
+    format!("/{}", parts.join("/"))
+}
+
+// handlers are shared, not copied: cloning a `Node` (and so a `Router`) only
+// bumps the refcount, and any state a closure captures is shared by every
+// clone. `Send + Sync` lets a router be used from several threads at once.
+pub type Handler<T = String> = Arc<dyn Fn(&Request) -> T + Send + Sync>;
+
+// what a `Router::route_fallible` handler fails with
+pub type HandlerError = Box<dyn Error + Send + Sync>;
+
+// see `Router::error_handler`
+pub type ErrorHandler<T = String> = Arc<dyn Fn(&(dyn Error + 'static)) -> T + Send + Sync>;
+
+// a route's handler as stored: a plain one ignores `Recover`, a fallible one
+// turns its error into a body with it
+type RouteHandler<T> = Arc<dyn Fn(&Request, &Recover<'_, T>) -> T + Send + Sync>;
+
+fn infallible<T: 'static>(handler: Handler<T>) -> RouteHandler<T> {
+    Arc::new(move |request: &Request, _: &Recover<'_, T>| handler(request))
+}
+
+// the resolving router's error handler, and where the error is kept for
+// `Match::error`
+struct Recover<'a, T> {
+    handler: Option<&'a ErrorHandler<T>>,
+    error: &'a RefCell<Option<String>>,
+}
+
+impl<T> Recover<'_, T> {
+    // `None` without an error handler
+    fn recover(&self, error: HandlerError) -> Option<T> {
+        *self.error.borrow_mut() = Some(error.to_string());
+        self.handler.map(|handler| handler(&*error))
+    }
+}
+
+// see `Router::guard`
+pub type Guard = Arc<dyn Fn(&GuardCtx) -> bool + Send + Sync>;
+
+// what a guard decides on: the request, without params yet, and the params
+// of the route being tried
+pub struct GuardCtx<'a> {
+    pub request: &'a Request,
+    pub params: &'a Params,
+}
+
+impl GuardCtx<'_> {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.request.header(name)
+    }
+}
+
+// anything a route can be registered with: plain `fn` items and closures,
+// taking nothing, the captured `&Params`, the whole `&Request`, the router's
+// `&S`, or `&S` and `&Request`. `M` only tells them apart and is always
+// inferred.
+pub trait IntoHandler<T, M, S = ()> {
+    fn into_handler(self, state: &S) -> Handler<T>;
+}
+
+pub struct NoArgs;
+pub struct WithParams;
+pub struct WithRequest;
+pub struct Shared;
+pub struct WithState;
+pub struct WithStateAndRequest;
+
+impl<T, S, F> IntoHandler<T, NoArgs, S> for F
+where
+    F: Fn() -> T + Send + Sync + 'static,
+{
+    fn into_handler(self, _: &S) -> Handler<T> {
+        Arc::new(move |_: &Request| self())
+    }
+}
+
+impl<T, S, F> IntoHandler<T, WithParams, S> for F
+where
+    F: Fn(&Params) -> T + Send + Sync + 'static,
+{
+    fn into_handler(self, _: &S) -> Handler<T> {
+        Arc::new(move |request: &Request| self(&request.params))
+    }
+}
+
+impl<T, S, F> IntoHandler<T, WithRequest, S> for F
+where
+    F: Fn(&Request) -> T + Send + Sync + 'static,
+{
+    fn into_handler(self, _: &S) -> Handler<T> {
+        Arc::new(self)
+    }
+}
+
+// an already built handler, e.g. from `handlers`, shared between routes
+impl<T, S> IntoHandler<T, Shared, S> for Handler<T> {
+    fn into_handler(self, _: &S) -> Handler<T> {
+        self
+    }
+}
+
+// see `Router::with_state`
+impl<T, S, F> IntoHandler<T, WithState, S> for F
+where
+    F: Fn(&S) -> T + Send + Sync + 'static,
+    S: Clone + Send + Sync + 'static,
+{
+    fn into_handler(self, state: &S) -> Handler<T> {
+        let state = state.clone();
+        Arc::new(move |_: &Request| self(&state))
+    }
+}
+
+impl<T, S, F> IntoHandler<T, WithStateAndRequest, S> for F
+where
+    F: Fn(&S, &Request) -> T + Send + Sync + 'static,
+    S: Clone + Send + Sync + 'static,
+{
+    fn into_handler(self, state: &S) -> Handler<T> {
+        let state = state.clone();
+        Arc::new(move |request: &Request| self(&state, request))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn route_adds_node() {
+        let router: Router = Router::default();
+        assert_eq!(0, router.nodes.len());
+
+        let router = Router::default()
+            .route(Method::GET, "/foo", || String::from("foo"))
+            .route(Method::GET, "/bar", || String::from("bar"));
+        assert_eq!(2, router.nodes.len())
+    }
+
+    #[test]
+    fn resolve_patch_head_and_options() {
+        let router = Router::default()
+            .patch("/patch", || String::from("patch"))
+            .head("/head", || String::from("head"))
+            .options("/options", || String::from("options"));
+
+        assert_eq!("patch", router.resolve("PATCH", "/patch").unwrap());
+        assert_eq!("head", router.resolve("head", "/head").unwrap());
+        assert_eq!("options", router.resolve("Options", "/options").unwrap());
+        assert_eq!(
+            Err(RouteError::MethodNotAllowed(vec![Method::PATCH])),
+            router.resolve("GET", "/patch")
+        );
+        for method in [Method::PATCH, Method::HEAD, Method::OPTIONS] {
+            assert_eq!(Ok(method.clone()), Method::try_from(method.as_str()));
+            assert_eq!(method.as_str(), method.to_string());
+        }
+    }
+
+    #[test]
+    fn resolve_returns_a_string() {
+        let router = Router::default()
+            .route(Method::GET, "/get", || String::from("get"))
+            .route(Method::POST, "/post", || String::from("post"))
+            .put("/put", || String::from("put"))
+            .delete("/delete", || String::from("delete"));
+
+        assert_eq!("get", router.resolve("GET", "/get").unwrap());
+        assert_eq!("post", router.resolve("POST", "/post").unwrap());
+        assert_eq!("put", router.resolve("PUT", "/put").unwrap());
+        assert_eq!("delete", router.resolve("DELETE", "/delete").unwrap());
+        assert_eq!(Err(RouteError::NotFound), router.resolve("GET", "/foo"));
+    }
+
+    #[test]
+    fn resolve_placeholder() {
+        let router = Router::default()
+            .route(Method::GET, "/foo/*", || String::from("foo"))
+            .route(Method::GET, "/foo/*/*/bar", || String::from("foobar"));
+
+        assert_eq!("foo", router.resolve("GET", "/foo/1").unwrap());
+        assert_eq!("foo", router.resolve("GET", "/foo/a").unwrap());
+        assert_eq!("foobar", router.resolve("GET", "/foo/1/2/bar").unwrap());
+        assert_eq!(
+            Err(RouteError::NotFound),
+            router.resolve("GET", "/foo/1/2/3/bar")
+        );
+        assert_eq!(
+            Err(RouteError::NotFound),
+            router.resolve("GET", "/foo/1/2/bar/3")
+        );
+    }
+
+    #[test]
+    fn normalize_in_one_pass() {
+        let strict = Options {
+            strict_slashes: true,
+            ..Options::default()
+        };
+        let preserve = Options {
+            preserve_double_slashes: true,
+            ..Options::default()
+        };
+        for (path, options, expected) in [
+            ("/foo////bar//", Options::default(), "/foo/bar"),
+            ("///", Options::default(), ""),
+            ("/café//ü/", Options::default(), "/café/ü"),
+            ("/foo//bar//", strict, "/foo/bar/"),
+            ("/foo//bar//", preserve, "/foo//bar/"),
+            ("/a/./b", Options::default(), "/a/b"),
+            ("/a/b/../c", Options::default(), "/a/c"),
+            ("/a/b/..", Options::default(), "/a"),
+            ("/a/b/..", strict, "/a/"),
+            ("/a/.%2E/%2e/b/.", strict, "/b/"),
+            ("/a/..b/.c", Options::default(), "/a/..b/.c"),
+            ("/a//../b", preserve, "/a/b"),
+            // clamped to the root
+            ("/../../admin", Options::default(), "/admin"),
+            ("/..", Options::default(), ""),
+        ] {
+            assert_eq!(expected, normalize(path, &options), "{}", path);
+        }
+    }
+
+    #[test]
+    fn dot_segments_resolve_before_matching() {
+        let router = Router::default()
+            .get("/foo/bar", || String::from("bar"))
+            .get("/static/*path", |params: &Params| {
+                params.get("path").unwrap().to_string()
+            });
+
+        assert_eq!("bar", router.resolve("GET", "/foo/./bar").unwrap());
+        assert_eq!("bar", router.resolve("GET", "/foo/x/../bar").unwrap());
+        assert_eq!("bar", router.resolve("GET", "/../foo/bar").unwrap());
+        assert_eq!(
+            "css/site.css",
+            router
+                .resolve("GET", "/static/js/../css/./site.css")
+                .unwrap()
+        );
+        assert_eq!(
+            Err(RouteError::NotFound),
+            router.resolve("GET", "/static/%2e%2e/etc/passwd")
+        );
+
+        for pattern in ["/a/./b", "/a/..", "/../a"] {
+            assert!(Pattern::parse(pattern).is_err(), "{}", pattern);
+        }
+    }
+
+    #[test]
+    fn consecutive_slashes_ignored() {
+        let router = Router::default().route(Method::GET, "/a/b/c", || String::from("abc"));
+
+        assert_eq!("abc", router.resolve("GET", "/a//////b//c").unwrap());
+    }
+
+    #[test]
+    fn trailing_slash() {
+        let router = Router::default()
+            .get("/foo", || String::from("foo"))
+            .get("/bar/", || String::from("bar"));
+
+        assert_eq!("foo", router.resolve("GET", "/foo").unwrap());
+        assert_eq!("foo", router.resolve("GET", "/foo/").unwrap());
+        assert_eq!("foo", router.resolve("GET", "/foo//").unwrap());
+        assert_eq!("bar", router.resolve("GET", "/bar").unwrap());
+        assert_eq!("bar", router.resolve("GET", "/bar/").unwrap());
+        assert_eq!("bar", router.resolve("GET", "/bar//").unwrap());
+    }
+
+    #[test]
+    fn strict_slashes_tell_routes_apart() {
+        let router = Router::default()
+            .strict_slashes(true)
+            .get("/", || String::from("root"))
+            .get("/foo", || String::from("foo"))
+            .get("/foo/", || String::from("foo dir"))
+            .get("/bar/", || String::from("bar"));
+
+        assert_eq!("root", router.resolve("GET", "/").unwrap());
+        assert_eq!("foo", router.resolve("GET", "/foo").unwrap());
+        assert_eq!("foo dir", router.resolve("GET", "/foo/").unwrap());
+        assert_eq!("foo dir", router.resolve("GET", "/foo//").unwrap());
+        assert_eq!("bar", router.resolve("GET", "/bar/").unwrap());
+        assert_eq!(
+            RouteError::NotFound,
+            router.resolve("GET", "/bar").unwrap_err()
+        );
+        assert_eq!(Ok(()), router.validate());
+    }
+
+    #[test]
+    fn double_slashes_can_be_preserved() {
+        let router = Router::default()
+            .preserve_double_slashes(true)
+            .get("/a/b", || String::from("ab"))
+            .get("/files/*path", |params: &Params| {
+                params.get("path").unwrap().to_string()
+            });
+
+        assert_eq!("ab", router.resolve("GET", "/a/b/").unwrap());
+        assert_eq!(
+            RouteError::NotFound,
+            router.resolve("GET", "/a//b").unwrap_err()
+        );
+        assert_eq!(
+            "https://x/y",
+            router.resolve("GET", "/files/https://x/y").unwrap()
+        );
+    }
+
+    #[test]
+    fn method_casings_normalize() {
+        for (input, expected) in [
+            ("GET", Method::GET),
+            ("get", Method::GET),
+            ("Get", Method::GET),
+            ("gEt", Method::GET),
+            ("POST", Method::POST),
+            ("Post", Method::POST),
+            ("put", Method::PUT),
+            ("DELETE", Method::DELETE),
+            ("Delete", Method::DELETE),
+            ("delete", Method::DELETE),
+        ] {
+            let method = Method::try_from(input).unwrap();
+            assert_eq!(expected, method);
+            assert_eq!(input.to_ascii_uppercase(), method.as_str());
+        }
+    }
+
+    #[test]
+    fn custom_method_is_uppercased() {
+        let method = Method::try_from("propfind").unwrap();
+        assert_eq!(Method::Custom(String::from("PROPFIND")), method);
+        assert_eq!("PROPFIND", method.as_str());
+        assert_eq!(method, Method::try_from("PropFind").unwrap());
+
+        assert!(Method::try_from("").is_err());
+        assert!(Method::try_from("GE T").is_err());
+        assert!(Method::try_from("GET\n").is_err());
+        assert_eq!(
+            Method::Custom(String::from("mkcol")),
+            Method::try_from("MKCOL").unwrap()
+        );
+        assert_eq!(Method::TRACE, Method::try_from("trace").unwrap());
+    }
+
+    #[test]
+    fn extension_methods_route() {
+        let router = Router::default()
+            .method("PROPFIND", "/files/*path", |params: &Params| {
+                format!("props of {}", params.get("path").unwrap())
+            })
+            .method("report", "/calendars/:id", || String::from("report"))
+            .route(Method::TRACE, "/", || String::from("trace"));
+
+        assert_eq!(
+            "props of a/b",
+            router.resolve("propfind", "/files/a/b").unwrap()
+        );
+        assert_eq!("report", router.resolve("REPORT", "/calendars/1").unwrap());
+        assert_eq!("trace", router.resolve("TRACE", "/").unwrap());
+        assert_eq!(
+            Err(RouteError::MethodNotAllowed(vec![Method::Custom(
+                String::from("PROPFIND")
+            )])),
+            router.resolve("MKCOL", "/files/a")
+        );
+    }
+
+    #[test]
+    fn resolve_catch_all() {
+        let router = Router::default()
+            .get("/static/**", || String::from("static"))
+            .get("/static/*/index", || String::from("index"));
+
+        assert_eq!("static", router.resolve("GET", "/static").unwrap());
+        assert_eq!("static", router.resolve("GET", "/static/a").unwrap());
+        assert_eq!("static", router.resolve("GET", "/static/a/b/c").unwrap());
+        assert_eq!(Err(RouteError::NotFound), router.resolve("GET", "/other/a"));
+    }
+
+    #[test]
+    fn match_prefix_returns_remainder() {
+        let router = Router::default()
+            .get("/api/**", || String::from("api"))
+            .get("/files/*", || String::from("files"));
+
+        let (node, rest) = router.match_prefix(Method::GET, "/api/users/1").unwrap();
+        assert_eq!("/api/**", node.pattern());
+        assert_eq!(&Method::GET, node.method());
+        assert_eq!("/users/1", rest);
+
+        assert_eq!("", router.match_prefix(Method::GET, "/api").unwrap().1);
+        assert_eq!("/", router.match_prefix(Method::GET, "/api/").unwrap().1);
+        assert_eq!(
+            "/raw//rest",
+            router
+                .match_prefix(Method::GET, "//api/raw//rest")
+                .unwrap()
+                .1
+        );
+        assert_eq!(
+            "/v2",
+            router.match_prefix(Method::GET, "/files/a/v2").unwrap().1
+        );
+
+        assert!(router.match_prefix(Method::GET, "/other").is_none());
+        assert!(router.match_prefix(Method::GET, "/files").is_none());
+        assert!(router.match_prefix(Method::POST, "/api/users").is_none());
+
+        // only the last `**` ends a prefix
+        let router = Router::default().get("/a/**/b/**", || String::from("a"));
+        assert!(router.match_prefix(Method::GET, "/a/x/y").is_none());
+        assert!(router.match_prefix(Method::GET, "/a/x/b/y").is_none());
+    }
+
+    #[test]
+    fn closure_handlers_share_state_across_clones() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let router = Router::default().get("/count", move || {
+            counter.fetch_add(1, Ordering::SeqCst).to_string()
+        });
+        let cloned = router.clone();
+
+        assert_eq!("0", router.resolve("GET", "/count").unwrap());
+        assert_eq!("1", cloned.resolve("GET", "/count").unwrap());
+        assert_eq!(2, calls.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn router_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Router>();
+        assert_send_sync::<Node>();
+    }
+
+    #[test]
+    fn route_map_transforms_output() {
+        let router = Router::default()
+            .route_map(
+                Method::GET,
+                "/quoted",
+                || String::from("foo"),
+                |s| format!("\"{}\"", s),
+            )
+            .get("/plain", || String::from("foo"));
+
+        assert_eq!("\"foo\"", router.resolve("GET", "/quoted").unwrap());
+        assert_eq!("foo", router.resolve("GET", "/plain").unwrap());
+    }
+
+    #[test]
+    fn static_segment_beats_param() {
+        let router = Router::default()
+            .get("/users/:id", || String::from("id"))
+            .get("/users/me", || String::from("me"));
+
+        assert_eq!("me", router.resolve("GET", "/users/me").unwrap());
+        assert_eq!("id", router.resolve("GET", "/users/42").unwrap());
+
+        let router = Router::default()
+            .get("/users/me", || String::from("me"))
+            .get("/users/:id", || String::from("id"));
+
+        assert_eq!("me", router.resolve("GET", "/users/me").unwrap());
+        assert_eq!("id", router.resolve("GET", "/users/42").unwrap());
+    }
+
+    #[test]
+    fn literal_segments_beat_wildcards_in_any_order() {
+        let router = Router::default()
+            .get("/users/*", || String::from("any user"))
+            .get("/a/*/*", || String::from("a * *"))
+            .get("/a/*/c", || String::from("a * c"))
+            .get("/users/me", || String::from("me"));
+
+        assert_eq!("me", router.resolve("GET", "/users/me").unwrap());
+        assert_eq!("any user", router.resolve("GET", "/users/42").unwrap());
+        assert_eq!("a * c", router.resolve("GET", "/a/b/c").unwrap());
+        assert_eq!("a * *", router.resolve("GET", "/a/b/d").unwrap());
+    }
+
+    #[test]
+    fn partial_wildcards_rank_between_literals_and_wildcards() {
+        let router = Router::default()
+            .get("/data/*", || String::from("any"))
+            .get("/data/*.json", |params: &Params| {
+                format!("json {}", params.wildcard(0).unwrap())
+            })
+            .get("/data/index.json", || String::from("index"))
+            .get("/data/:file:int", || String::from("int"));
+
+        assert_eq!("index", router.resolve("GET", "/data/index.json").unwrap());
+        assert_eq!(
+            "json stats",
+            router.resolve("GET", "/data/stats.json").unwrap()
+        );
+        assert_eq!("any", router.resolve("GET", "/data/stats.csv").unwrap());
+        assert_eq!("int", router.resolve("GET", "/data/42").unwrap());
+        assert!(router.validate().is_ok());
+
+        // no path ends in both .json and .csv, but report-1.csv fits two
+        let router = Router::default()
+            .get("/r/*.json", || String::from("json"))
+            .get("/r/*.csv", || String::from("csv"))
+            .get("/r/report-*", || String::from("report"));
+        assert_eq!(
+            vec![(0, 2), (1, 2)],
+            router
+                .conflicts()
+                .iter()
+                .map(|conflict| (conflict.first, conflict.second))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!("csv", router.resolve("GET", "/r/report-1.csv").unwrap());
+        assert_eq!("report", router.resolve("GET", "/r/report-1").unwrap());
+    }
+
+    #[test]
+    fn try_route_refuses_shadowed_routes() {
+        let ok = || String::from("ok");
+        let router = Router::default()
+            .try_route(Method::GET, "/users/*", ok)
+            .unwrap();
+        assert_eq!(
+            TryRouteError::Conflict(Conflict {
+                method: MethodMatcher::Only(Method::GET),
+                first: 0,
+                second: 1,
+                first_pattern: String::from("/users/*"),
+                second_pattern: String::from("/users/*"),
+            }),
+            router
+                .clone()
+                .try_route(Method::GET, "/users/*", ok)
+                .unwrap_err()
+        );
+        let error = router
+            .clone()
+            .try_route(Method::GET, "/users/:id", ok)
+            .unwrap_err();
+        assert_eq!(
+            "GET `/users/:id` conflicts with #0 `/users/*`",
+            error.to_string()
+        );
+
+        let router = router
+            .try_route(Method::POST, "/users/*", ok)
+            .unwrap()
+            .try_route(Method::GET, "/users/me", ok)
+            .unwrap()
+            .try_route(Method::GET, "/foo", ok)
+            .unwrap();
+        assert!(matches!(
+            router.try_route(Method::GET, "/foo/", ok),
+            Err(TryRouteError::Conflict(Conflict {
+                first: 3,
+                second: 4,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn try_route_refuses_malformed_patterns() {
+        let ok = || String::from("ok");
+        let try_route = |pattern| match Router::default().try_route(Method::GET, pattern, ok) {
+            Ok(router) => Ok(router.routes().next().unwrap().pattern().to_string()),
+            Err(TryRouteError::Pattern(error)) => Err((error.segment, error.message)),
+            Err(error) => panic!("{}", error),
+        };
+        let error = |segment, message: &str| Err((segment, message.to_string()));
+
+        assert_eq!(error(0, "empty pattern"), try_route(""));
+        assert_eq!(
+            error(0, "a pattern starts with `/`"),
+            try_route("users/:id")
+        );
+        assert_eq!(error(1, "empty segment"), try_route("//foo"));
+        assert_eq!(error(2, "empty segment"), try_route("/a//b"));
+        assert_eq!(error(2, "empty segment"), try_route("/a//"));
+        for pattern in ["/a b", "/a/b\t", "/a\n", "/\0"] {
+            assert_eq!(
+                "whitespace or control character in segment",
+                try_route(pattern).unwrap_err().1,
+                "{:?}",
+                pattern
+            );
+        }
+        assert_eq!(error(2, "missing param name"), try_route("/a/:"));
+
+        // still trimmed, not refused
+        assert_eq!(Ok(String::from("/users")), try_route("/users/"));
+        assert_eq!(Ok(String::new()), try_route("/"));
+        assert!(Router::default()
+            .try_route(Method::GET, "/a//b", ok)
+            .unwrap_err()
+            .to_string()
+            .starts_with("invalid pattern `/a//b` at segment 2"));
+    }
+
+    #[test]
+    fn url_for_fills_named_routes() {
+        let ok = || String::from("ok");
+        let router = Router::default()
+            .get("/", ok)
+            .name("home")
+            .get("/users/*/posts/:post/", ok)
+            .name("user_post")
+            .get("/files/*path", ok)
+            .name("file")
+            .get("/docs/:page?", ok)
+            .name("docs");
+
+        assert_eq!(Ok(String::from("/")), router.url_for("home", &[]));
+        assert_eq!(
+            Ok(String::from("/users/42/posts/7")),
+            router.url_for("user_post", &["42", "7"])
+        );
+        let url = router.url_for("user_post", &["a/b c", "7"]).unwrap();
+        assert_eq!("/users/a%2Fb%20c/posts/7", url);
+        assert_eq!("ok", router.resolve("GET", &url).unwrap());
+        assert_eq!(
+            Ok(String::from("/files/css/a%20b.css")),
+            router.url_for("file", &["css/a b.css"])
+        );
+        assert_eq!(Ok(String::from("/docs")), router.url_for("docs", &[]));
+        assert_eq!(
+            Ok(String::from("/docs/intro")),
+            router.url_for("docs", &["intro"])
+        );
+
+        assert_eq!(
+            Err(UrlForError::UnknownName(String::from("user"))),
+            router.url_for("user", &["42"])
+        );
+        assert_eq!(
+            Err(UrlForError::WrongArgumentCount {
+                expected: 2,
+                got: 1
+            }),
+            router.url_for("user_post", &["42"])
+        );
+        assert_eq!(
+            Err(UrlForError::EmptyArgument { index: 1 }),
+            router.url_for("user_post", &["42", ""])
+        );
+
+        let router = Router::default()
+            .strict_slashes(true)
+            .get("/users/:id/", ok)
+            .name("user");
+        assert_eq!(
+            Ok(String::from("/users/42/")),
+            router.url_for("user", &["42"])
+        );
+    }
+
+    #[test]
+    fn regex_constraints_fall_through() {
+        let router = Router::default()
+            .get("/users/*", || String::from("any"))
+            .get("/users/{id:\\d+}", |params: &Params| {
+                format!("user {}", params.get("id").unwrap())
+            });
+
+        assert_eq!("user 12", router.resolve("GET", "/users/12").unwrap());
+        assert_eq!("any", router.resolve("GET", "/users/12abc").unwrap());
+        assert_eq!("any", router.resolve("GET", "/users/avatar.png").unwrap());
+    }
+
+    #[test]
+    fn conflicts_reports_ambiguous_params() {
+        let router = Router::default()
+            .get("/users/me", || String::from("me"))
+            .get("/users/:id", || String::from("id"))
+            .get("/users/:name", || String::from("name"))
+            .post("/users/:name", || String::from("post"));
+
+        assert_eq!(
+            vec![Conflict {
+                method: MethodMatcher::Only(Method::GET),
+                first: 1,
+                second: 2,
+                first_pattern: String::from("/users/:id"),
+                second_pattern: String::from("/users/:name"),
+            }],
+            router.conflicts()
+        );
+        assert_eq!("id", router.resolve("GET", "/users/bob").unwrap());
+
+        let router = Router::default()
+            .get("/a/:x/b", || String::from("b"))
+            .get("/a/:y/c", || String::from("c"));
+        assert!(router.conflicts().is_empty());
+    }
+
+    #[test]
+    fn resolve_or_calls_default_only_on_miss() {
+        let router = Router::default().get("/foo", || String::from("foo"));
+
+        assert_eq!(
+            "foo",
+            router.resolve_or("GET", "/foo", || unreachable!("default called"))
+        );
+        assert_eq!(
+            "fallback",
+            router.resolve_or("GET", "/bar", || String::from("fallback"))
+        );
+    }
+
+    #[test]
+    fn handler_receives_params() {
+        let router = Router::default().get("/users/:id", |params: &Params| {
+            format!("user {}", params.get("id").unwrap())
+        });
+
+        assert_eq!("user 42", router.resolve("GET", "/users/42").unwrap());
+    }
+
+    #[test]
+    fn host_groups_ignore_the_port() {
+        let router = Router::default()
+            .host("api.example.com", |api| {
+                api.get("/users", || String::from("api users"))
+            })
+            .host("www.example.com", |www| {
+                www.get("/users", || String::from("www users"))
+            })
+            .get("/users", || String::from("users"))
+            .get("/about", || String::from("about"));
+
+        for (host, expected) in [
+            ("api.example.com", "api users"),
+            ("www.example.com:8080", "www users"),
+            ("API.EXAMPLE.COM:443", "api users"),
+            ("other.example.com", "users"),
+            ("[::1]:8080", "users"),
+        ] {
+            assert_eq!(
+                expected,
+                router.resolve_with_host("GET", host, "/users").unwrap(),
+                "{}",
+                host
+            );
+        }
+        assert_eq!("users", router.resolve("GET", "/users").unwrap());
+        assert_eq!(
+            "about",
+            router
+                .resolve_with_host("GET", "api.example.com:80", "/about")
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn wildcard_host_captures_subdomain() {
+        let router = Router::default()
+            .route_on_host("*.example.com", Method::GET, "/", |params: &Params| {
+                format!("tenant {}", params.get("subdomain").unwrap())
+            })
+            .route_on_host("www.example.com", Method::GET, "/", || String::from("www"))
+            .get("/", || String::from("any host"));
+
+        assert_eq!(
+            "tenant acme",
+            router
+                .resolve_with_host("GET", "acme.example.com", "/")
+                .unwrap()
+        );
+        assert_eq!(
+            "tenant acme",
+            router
+                .resolve_with_host("GET", "ACME.Example.COM", "/")
+                .unwrap()
+        );
+        assert_eq!(
+            "www",
+            router
+                .resolve_with_host("GET", "www.example.com", "/")
+                .unwrap()
+        );
+
+        // a bare domain or several labels fall back to the host-less route
+        assert_eq!(
+            "any host",
+            router.resolve_with_host("GET", "example.com", "/").unwrap()
+        );
+        assert_eq!(
+            "any host",
+            router
+                .resolve_with_host("GET", "a.b.example.com", "/")
+                .unwrap()
+        );
+        assert_eq!(
+            "any host",
+            router
+                .resolve_with_host("GET", "badexample.com", "/")
+                .unwrap()
+        );
+
+        // without a host only host-less routes match
+        assert_eq!("any host", router.resolve("GET", "/").unwrap());
+        let router = Router::default()
+            .route_on_host("*.example.com", Method::GET, "/", || String::from("tenant"));
+        assert_eq!(Err(RouteError::NotFound), router.resolve("GET", "/"));
+    }
+
+    #[test]
+    fn int_and_enum_constraints() {
+        let router = Router::default()
+            .get("/items/:id:int", || String::from("id"))
+            .get("/pages/:page:int(1..100)", |params: &Params| {
+                format!("page {}", params.get("page").unwrap())
+            })
+            .get("/pages/:page:int(100..=200)", || String::from("high page"))
+            .get("/pages/*", || String::from("other page"))
+            .get("/posts/:status:enum(active|archived)", |params: &Params| {
+                params.get("status").unwrap().to_string()
+            });
+
+        assert_eq!("id", router.resolve("GET", "/items/-3").unwrap());
+        assert_eq!(
+            Err(RouteError::NotFound),
+            router.resolve("GET", "/items/abc")
+        );
+
+        assert_eq!("page 1", router.resolve("GET", "/pages/1").unwrap());
+        assert_eq!("page 99", router.resolve("GET", "/pages/99").unwrap());
+        assert_eq!("high page", router.resolve("GET", "/pages/100").unwrap());
+        assert_eq!("high page", router.resolve("GET", "/pages/200").unwrap());
+        assert_eq!("other page", router.resolve("GET", "/pages/0").unwrap());
+        assert_eq!("other page", router.resolve("GET", "/pages/201").unwrap());
+        assert_eq!("other page", router.resolve("GET", "/pages/ten").unwrap());
+
+        assert_eq!("active", router.resolve("GET", "/posts/active").unwrap());
+        assert_eq!(
+            "archived",
+            router.resolve("GET", "/posts/archived").unwrap()
+        );
+        assert_eq!(
+            Err(RouteError::NotFound),
+            router.resolve("GET", "/posts/deleted")
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "unknown constraint")]
+    fn route_panics_on_invalid_constraint() {
+        Router::default().get("/a/:id:float", || String::from("a"));
+    }
+
+    #[test]
+    fn resolve_line_parses_request_lines() {
+        let router = Router::default().get("/foo/bar", || String::from("foobar"));
+
+        assert_eq!(
+            "foobar",
+            router.resolve_line("GET /foo/bar HTTP/1.1").unwrap()
+        );
+        assert_eq!("foobar", router.resolve_line("GET /foo/bar").unwrap());
+        assert_eq!(
+            "foobar",
+            router
+                .resolve_line("  GET   /foo/bar \t HTTP/1.0\r\n")
+                .unwrap()
+        );
+        assert_eq!(
+            Err(RouteError::MethodNotAllowed(vec![Method::GET])),
+            router.resolve_line("POST /foo/bar HTTP/1.1")
+        );
+        assert_eq!(
+            Err(RouteError::InvalidMethod(String::from("G(T"))),
+            router.resolve_line("G(T /foo/bar HTTP/1.1")
+        );
+
+        for line in [
+            "",
+            "GET",
+            "/foo/bar",
+            "GET foo/bar HTTP/1.1",
+            "GET /foo/bar SPDY/3",
+            "GET /foo/bar HTTP/1.1 extra",
+        ] {
+            assert_eq!(
+                Err(RouteError::BadRequest(String::from(
+                    "malformed request line"
+                ))),
+                router.resolve_line(line),
+                "{:?}",
+                line
+            );
+        }
+    }
+
+    #[test]
+    fn resolve_takes_any_request_target() {
+        let router = Router::default()
+            .get("/foo", |request: &Request| {
+                format!("foo {} {:?}", request.path, request.query)
+            })
+            .post("/foo", || String::from("post"))
+            .route_on_host("api.example.com", Method::GET, "/foo", || {
+                String::from("api foo")
+            });
+
+        // origin-form, with the fragment dropped
+        assert_eq!("foo /foo None", router.resolve("GET", "/foo#top").unwrap());
+        // absolute-form, routed on its host
+        assert_eq!(
+            "foo /foo Some(\"a=1\")",
+            router
+                .resolve("GET", "http://example.com:8080/foo?a=1#top")
+                .unwrap()
+        );
+        assert_eq!(
+            "api foo",
+            router
+                .resolve("GET", "https://api.example.com/foo")
+                .unwrap()
+        );
+        assert_eq!(
+            "api foo",
+            router
+                .resolve_with_host("GET", "example.com", "http://API.example.com:443/foo")
+                .unwrap()
+        );
+        assert_eq!(
+            "api foo",
+            router
+                .resolve_line("GET http://api.example.com/foo HTTP/1.1")
+                .unwrap()
+        );
+        assert_eq!(
+            Err(RouteError::NotFound),
+            router.resolve("GET", "http://example.com")
+        );
+
+        // asterisk-form
+        assert_eq!(
+            Err(RouteError::MethodNotAllowed(vec![
+                Method::GET,
+                Method::POST
+            ])),
+            router.resolve("OPTIONS", "*")
+        );
+        assert_eq!(
+            "GET, HEAD, POST",
+            router
+                .clone()
+                .auto_options(true)
+                .resolve("OPTIONS", "*")
+                .unwrap()
+        );
+        assert_eq!(
+            Err(RouteError::NotFound),
+            Router::<String>::default().resolve("OPTIONS", "*")
+        );
+        assert_eq!(
+            Err(RouteError::BadRequest(String::from(
+                "`*` is only a target for OPTIONS"
+            ))),
+            router.resolve("GET", "*")
+        );
+
+        for target in ["://nope", "foo", "http:///foo"] {
+            assert_eq!(
+                Err(RouteError::BadRequest(String::from(
+                    "malformed request-target"
+                ))),
+                router.resolve("GET", target),
+                "{:?}",
+                target
+            );
+        }
+    }
+
+    #[test]
+    fn cache_is_invisible() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counted = calls.clone();
+        let routes = Router::default()
+            .get("/users/:id", move |params: &Params| {
+                counted.fetch_add(1, Ordering::Relaxed);
+                format!("user {}", params.get("id").unwrap())
+            })
+            .get("/users/me", || String::from("me"))
+            .get("/files/**", || String::from("file"))
+            .route_on_host("*.example.com", Method::GET, "/", |params: &Params| {
+                format!("tenant {}", params.get("subdomain").unwrap())
+            });
+        let cached = routes.clone().with_cache(2);
+
+        for router in [&routes, &cached] {
+            for _ in 0..3 {
+                assert_eq!(
+                    routes.resolve_full("GET", "/users/1"),
+                    router.resolve_full("GET", "/users/1")
+                );
+                assert_eq!(
+                    routes.resolve_full("HEAD", "/users//2/"),
+                    router.resolve_full("HEAD", "/users//2/")
+                );
+                assert_eq!("me", router.resolve("GET", "/users/me").unwrap());
+                assert_eq!("file", router.resolve("GET", "/files/a/b").unwrap());
+                assert_eq!(
+                    Err(RouteError::MethodNotAllowed(vec![Method::GET])),
+                    router.resolve("POST", "/users/1")
+                );
+                for tenant in ["a", "b"] {
+                    assert_eq!(
+                        format!("tenant {}", tenant),
+                        router
+                            .resolve_with_host("GET", &format!("{}.example.com", tenant), "/")
+                            .unwrap()
+                    );
+                }
+            }
+        }
+        // the handler ran on every resolve, cached or not
+        assert_eq!(24, calls.load(Ordering::Relaxed));
+
+        // changing the routes or the options empties it
+        let mut changed = cached.clone().get("/users/1", || String::from("one"));
+        assert_eq!("one", changed.resolve("GET", "/users/1").unwrap());
+        assert!(changed.remove(Method::GET, "/users/1"));
+        assert_eq!("user 1", changed.resolve("GET", "/users/1").unwrap());
+        let strict = cached.clone().strict(true);
+        assert_eq!(Err(RouteError::NotFound), strict.resolve("GET", "/files"));
+        assert_eq!("file", cached.resolve("GET", "/files").unwrap());
+
+        // guards look at more than the path
+        let guarded = cached
+            .get("/beta", || String::from("beta"))
+            .guard(|ctx| ctx.header("x-beta").is_some())
+            .get("/beta", || String::from("stable"));
+        let request = || Request::new(Method::GET, "/beta");
+        assert_eq!("stable", guarded.resolve_request(request()).unwrap());
+        assert_eq!(
+            "beta",
+            guarded
+                .resolve_request(request().with_header("X-Beta", "1"))
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn handlers_share_the_router_state() {
+        use std::sync::Mutex;
+
+        type Log = Arc<Mutex<Vec<String>>>;
+        let log = Log::default();
+        let router = Router::with_state(log.clone())
+            .get("/count", |log: &Log| log.lock().unwrap().len().to_string())
+            .post("/items/:item", |log: &Log, request: &Request| {
+                let item = request.params.get("item").unwrap();
+                log.lock().unwrap().push(item.to_string());
+                format!("added {}", item)
+            })
+            .get("/", || String::from("home"))
+            .scope("/admin", |admin| {
+                admin.delete("/items", |log: &Log| {
+                    log.lock().unwrap().clear();
+                    String::from("cleared")
+                })
+            })
+            .not_found(|log: &Log| format!("{} items, no route", log.lock().unwrap().len()));
+
+        assert_eq!("added a", router.resolve("POST", "/items/a").unwrap());
+        assert_eq!(
+            "added b",
+            router.clone().resolve("POST", "/items/b").unwrap()
+        );
+        assert_eq!("2", router.resolve("GET", "/count").unwrap());
+        assert_eq!("home", router.resolve("GET", "/").unwrap());
+        assert_eq!(
+            "2 items, no route",
+            router.resolve("GET", "/missing").unwrap()
+        );
+        assert_eq!(vec!["a", "b"], *log.lock().unwrap());
+
+        assert_eq!("cleared", router.resolve("DELETE", "/admin/items").unwrap());
+        assert!(log.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn routes_are_removed_and_replaced_in_place() {
+        let mut router = Router::default()
+            .get("/plugins/a", || String::from("a"))
+            .post("/plugins/a", || String::from("post a"))
+            .get("/plugins/b/", || String::from("b"))
+            .get("/plugins/:name", |params: &Params| {
+                format!("plugin {}", params.get("name").unwrap())
+            });
+        assert_eq!("a", router.resolve("GET", "/plugins/a").unwrap());
+
+        assert!(router.remove(Method::GET, "/plugins/a"));
+        assert_eq!("plugin a", router.resolve("GET", "/plugins/a").unwrap());
+        assert_eq!("post a", router.resolve("POST", "/plugins/a").unwrap());
+        assert!(!router.remove(Method::GET, "/plugins/a"));
+        assert!(!router.remove(Method::PUT, "/plugins/b"));
+        assert!(!router.remove(Method::GET, "/plugins/:id:"));
+
+        // trimmed like `route` trims it
+        assert!(router.remove(Method::GET, "/plugins/b"));
+        assert!(router.remove(Method::GET, "/plugins/:name/"));
+        assert_eq!(
+            Err(RouteError::MethodNotAllowed(vec![Method::POST])),
+            router.resolve("GET", "/plugins/a")
+        );
+        assert_eq!(
+            Err(RouteError::NotFound),
+            router.resolve("GET", "/plugins/b")
+        );
+
+        let before = router.clone();
+        assert!(router.replace(Method::POST, "/plugins/a/", || String::from("new a")));
+        assert!(!router.replace(Method::GET, "/plugins/a", || String::from("new a")));
+        assert_eq!("new a", router.resolve("POST", "/plugins/a").unwrap());
+        assert_eq!("post a", before.resolve("POST", "/plugins/a").unwrap());
+
+        router.clear();
+        assert!(router.is_empty());
+        assert_eq!(
+            Err(RouteError::NotFound),
+            router.resolve("POST", "/plugins/a")
+        );
+    }
+
+    #[test]
+    fn guards_pick_between_routes_of_one_pattern() {
+        let internal = |ctx: &GuardCtx| ctx.header("x-internal").is_some();
+        let router = Router::default()
+            .get("/admin", || String::from("internal"))
+            .guard(internal)
+            .get("/admin", || String::from("public"))
+            .guard(move |ctx| !internal(ctx))
+            .get("/beta/:page", |params: &Params| {
+                format!("beta {}", params.get("page").unwrap())
+            })
+            .guard(|ctx| ctx.request.query_params().get("beta") == Some("1"))
+            .guard(|ctx| ctx.params.get("page") != Some("billing"))
+            .get("/beta/*", || String::from("stable"))
+            .post("/ops", || String::from("ops"))
+            .guard(internal);
+        let resolve = |request: Request| router.resolve_request(request);
+        let get = |path| Request::new(Method::GET, path);
+
+        assert_eq!(
+            Ok(String::from("internal")),
+            resolve(get("/admin").with_header("X-Internal", "1"))
+        );
+        assert_eq!(Ok(String::from("public")), resolve(get("/admin")));
+        assert_eq!(
+            Ok(String::from("beta home")),
+            resolve(get("/beta/home?beta=1"))
+        );
+        // both guards have to pass
+        assert_eq!(
+            Ok(String::from("stable")),
+            resolve(get("/beta/billing?beta=1"))
+        );
+        assert_eq!(Ok(String::from("stable")), resolve(get("/beta/home")));
+        assert_eq!(
+            Err(RouteError::NotFound),
+            resolve(Request::new(Method::POST, "/ops"))
+        );
+        assert_eq!(
+            Err(RouteError::MethodNotAllowed(vec![Method::GET])),
+            resolve(Request::new(Method::POST, "/admin"))
+        );
+        assert!(router.validate().is_ok());
+        assert!(Router::default()
+            .get("/admin", String::new)
+            .guard(internal)
+            .try_route(Method::GET, "/admin", String::new)
+            .is_ok());
+    }
+
+    #[test]
+    fn route_with_headers_surfaces_headers() {
+        let router = Router::default()
+            .route_with_headers(
+                Method::GET,
+                "/cached/:id/",
+                || String::from("cached"),
+                vec![(String::from("Cache-Control"), String::from("max-age=60"))],
+            )
+            .get("/plain", || String::from("plain"));
+
+        let matched = router.resolve_full("GET", "/cached/1").unwrap();
+        assert_eq!(Method::GET, matched.method);
+        assert_eq!("/cached/:id", matched.pattern);
+        assert_eq!(Some("1"), matched.params.get("id"));
+        assert_eq!(
+            vec![(String::from("Cache-Control"), String::from("max-age=60"))],
+            matched.headers
+        );
+        assert_eq!("cached", matched.body);
+        assert_eq!("cached", router.resolve("GET", "/cached/1").unwrap());
+
+        assert!(router
+            .resolve_full("GET", "/plain")
+            .unwrap()
+            .headers
+            .is_empty());
+        assert_eq!(
+            Err(RouteError::NotFound),
+            router.resolve_full("GET", "/nope")
+        );
+        assert_eq!(
+            Err(RouteError::InvalidMethod(String::from("G T"))),
+            router.resolve_full("G T", "/plain")
+        );
+    }
+
+    #[test]
+    fn multibyte_segments() {
+        let router = Router::default()
+            .get("/café/:name", |params: &Params| {
+                params.get("name").unwrap().to_string()
+            })
+            .get("/日本/**", || String::from("japan"));
+
+        assert_eq!("crème", router.resolve("GET", "/café/crème").unwrap());
+        assert_eq!("東京", router.resolve("GET", "//café//東京/").unwrap());
+        assert_eq!("japan", router.resolve("GET", "/日本/東京/渋谷").unwrap());
+        assert_eq!(
+            Err(RouteError::NotFound),
+            router.resolve("GET", "/cafe/crème")
+        );
+        assert_eq!(
+            "/東京/渋谷",
+            router
+                .match_prefix(Method::GET, "/日本/東京/渋谷")
+                .unwrap()
+                .1
+        );
+    }
+
+    #[test]
+    fn case_insensitive_multibyte() {
+        let router = Router::default()
+            .get("/Café/:name", |params: &Params| {
+                params.get("name").unwrap().to_string()
+            })
+            .case_insensitive(true);
+
+        assert_eq!("Crème", router.resolve("GET", "/CAFé/Crème").unwrap());
+        assert_eq!("Crème", router.resolve("GET", "/café/Crème").unwrap());
+        #[cfg(not(feature = "unicode"))]
+        assert_eq!(
+            Err(RouteError::NotFound),
+            router.resolve("GET", "/CAFÉ/Crème")
+        );
+    }
+
+    #[cfg(feature = "unicode")]
+    #[test]
+    fn case_insensitive_unicode_folding() {
+        let router = Router::default()
+            .get("/Straße", || String::from("street"))
+            .get("/Café", || String::from("cafe"))
+            .get("/σίσυφος", || String::from("sisyphus"))
+            .case_insensitive(true);
+
+        assert_eq!("street", router.resolve("GET", "/STRASSE").unwrap());
+        assert_eq!("cafe", router.resolve("GET", "/CAFÉ").unwrap());
+        assert_eq!("sisyphus", router.resolve("GET", "/ΣΊΣΥΦΟΣ").unwrap());
+    }
+
+    #[test]
+    fn params_extract_tuples() {
+        let router = Router::default().get("/posts/:id/:slug", |params: &Params| {
+            match params.extract::<(u64, String)>() {
+                Ok((id, slug)) => format!("{} {}", id + 1, slug),
+                Err(e) => e.to_string(),
+            }
+        });
+
+        assert_eq!(
+            "43 hello",
+            router.resolve("GET", "/posts/42/hello").unwrap()
+        );
+        assert_eq!(
+            "invalid value `abc` for param `id`: invalid digit found in string",
+            router.resolve("GET", "/posts/abc/hello").unwrap()
+        );
+
+        let params = router.resolve_full("GET", "/posts/1/a").unwrap().params;
+        assert_eq!(Ok((1u8,)), params.extract());
+        assert_eq!(
+            Ok((1, String::from("a"))),
+            params.extract::<(i32, String)>()
+        );
+        assert_eq!(
+            Err(ParamError::Missing { index: 2 }),
+            params.extract::<(u64, String, String)>()
+        );
+    }
+
+    #[test]
+    fn params_get_as_parses_by_name() {
+        struct Slug(String);
+        impl FromParam for Slug {
+            fn from_param(value: &str) -> Result<Self, String> {
+                match value.chars().all(|c| c.is_ascii_lowercase() || c == '-') {
+                    true => Ok(Slug(value.to_string())),
+                    false => Err(String::from("not a slug")),
+                }
+            }
+        }
+
+        let router = Router::default().get("/posts/:id/:slug", String::new);
+        let params = router
+            .resolve_full("GET", "/posts/42/hello-world")
+            .unwrap()
+            .params;
+        assert_eq!(Ok(42u32), params.get_as("id"));
+        assert_eq!("hello-world", params.get_as::<Slug>("slug").unwrap().0);
+
+        let params = router.resolve_full("GET", "/posts/abc/Hi").unwrap().params;
+        assert_eq!(
+            Err(ParamError::Invalid {
+                name: String::from("id"),
+                value: String::from("abc"),
+                message: String::from("invalid digit found in string"),
+            }),
+            params.get_as::<u32>("id")
+        );
+        assert_eq!(
+            "invalid value `Hi` for param `slug`: not a slug",
+            params.get_as::<Slug>("slug").err().unwrap().to_string()
+        );
+        assert_eq!(
+            Err(ParamError::Unknown {
+                name: String::from("page"),
+            }),
+            params.get_as::<u32>("page")
+        );
+    }
+
+    #[test]
+    fn routers_are_shared_across_threads() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Router>();
+        assert_send_sync::<Router<Response>>();
+
+        let router = Arc::new(Router::default().get("/users/:id", |params: &Params| {
+            format!("user {}", params.get("id").unwrap())
+        }));
+        let threads: Vec<_> = (0..8)
+            .map(|i| {
+                let router = router.clone();
+                std::thread::spawn(move || {
+                    (0..100).all(|_| {
+                        router.resolve("GET", &format!("/users/{}", i)).unwrap()
+                            == format!("user {}", i)
+                    })
+                })
+            })
+            .collect();
+        assert!(threads.into_iter().all(|thread| thread.join().unwrap()));
+
+        // clones share the routes and index until one changes
+        let copy = (*router).clone();
+        assert!(Arc::ptr_eq(&router.nodes, &copy.nodes));
+        assert!(Arc::ptr_eq(&router.index, &copy.index));
+        let copy = copy.get("/", || String::from("home"));
+        assert!(!Arc::ptr_eq(&router.nodes, &copy.nodes));
+        assert_eq!((1, 2), (router.len(), copy.len()));
+        assert!(router.resolve("GET", "/").is_err());
+        assert_eq!("home", copy.resolve("GET", "/").unwrap());
+    }
+
+    #[test]
+    fn match_lists_wildcards_in_order() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+        let router = Router::default().get("/files/*/v/:version/*/**", || {
+            CALLS.fetch_add(1, Ordering::Relaxed);
+            String::from("file")
+        });
+
+        let matched = router
+            .resolve_full("GET", "/files/a%20b/v/2/c/d/e")
+            .unwrap();
+        assert_eq!(
+            ("/files/*/v/:version/*/**", Method::GET, "file"),
+            (
+                matched.pattern.as_str(),
+                matched.method,
+                matched.body.as_str()
+            )
+        );
+        assert_eq!(
+            vec!["a b", "c", "d/e"],
+            matched.params.wildcards().collect::<Vec<_>>()
+        );
+        assert_eq!(Some("c"), matched.params.wildcard(1));
+        assert_eq!(Some("2"), matched.params.get("version"));
+
+        let params = router.resolve_full("GET", "/files/a/v/2/c").unwrap().params;
+        assert_eq!(vec!["a", "c", ""], params.wildcards().collect::<Vec<_>>());
+
+        assert_eq!(2, CALLS.load(Ordering::Relaxed));
+        assert!(router.resolve_full("GET", "/files/a/v/2").is_err());
+        assert!(router.resolve_full("POST", "/files/a/v/2/c").is_err());
+        assert_eq!(2, CALLS.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn match_reports_route_index() {
+        let router = Router::default()
+            .get("/a", || String::from("a"))
+            .post("/a", || String::from("post a"))
+            .get("/b/*", || String::from("b"))
+            .get("/b/c", || String::from("c"));
+
+        assert_eq!(0, router.resolve_full("GET", "/a").unwrap().index);
+        assert_eq!(1, router.resolve_full("POST", "/a").unwrap().index);
+        assert_eq!(2, router.resolve_full("GET", "/b/x").unwrap().index);
+        assert_eq!(3, router.resolve_full("GET", "/b/c").unwrap().index);
+    }
+
+    #[test]
+    fn encoded_slash_in_param() {
+        let router = Router::default()
+            .get("/files/:name", |params: &Params| {
+                params.get("name").unwrap().to_string()
+            })
+            .get("/files/*/meta", || String::from("meta"));
+
+        assert_eq!("a/b", router.resolve("GET", "/files/a%2Fb").unwrap());
+        assert_eq!(
+            "my report.txt",
+            router.resolve("GET", "/files/my%20report.txt").unwrap()
+        );
+        assert_eq!("meta", router.resolve("GET", "/files/a%2Fb/meta").unwrap());
+        assert_eq!(
+            Err(RouteError::NotFound),
+            router.resolve("GET", "/files/a/b")
+        );
+    }
+
+    #[test]
+    fn unknown_method_policy() {
+        let router = Router::default().get("/foo", || String::from("foo")).route(
+            Method::Custom(String::from("PURGE")),
+            "/cache",
+            || String::from("purged"),
+        );
+
+        // by default an unrouted method is just a miss, a 405 as GET /foo exists
+        assert_eq!(
+            Err(RouteError::MethodNotAllowed(vec![Method::GET])),
+            router.resolve_full("POST", "/foo")
+        );
+        assert_eq!(
+            Err(RouteError::MethodNotAllowed(vec![Method::GET])),
+            router.resolve_full("BREW", "/foo")
+        );
+        assert_eq!(
+            Err(RouteError::NotFound),
+            router.resolve_full("BREW", "/bar")
+        );
+
+        let router = router.unknown_method(UnknownMethod::NotImplemented);
+        assert_eq!(
+            Err(RouteError::NotImplemented(Method::Custom(String::from(
+                "BREW"
+            )))),
+            router.resolve_full("brew", "/foo")
+        );
+        assert_eq!(
+            Err(RouteError::NotImplemented(Method::POST)),
+            router.resolve_full("POST", "/foo")
+        );
+        // a routed method on an unknown path is still a 404
+        assert_eq!(
+            Err(RouteError::NotFound),
+            router.resolve_full("GET", "/bar")
+        );
+        assert_eq!(
+            Err(RouteError::MethodNotAllowed(vec![Method::GET])),
+            router.resolve_full("purge", "/foo")
+        );
+        assert_eq!("purged", router.resolve("purge", "/cache").unwrap());
+        assert_eq!(
+            Err(RouteError::NotImplemented(Method::Custom(String::from(
+                "BREW"
+            )))),
+            router.resolve("BREW", "/foo")
+        );
+    }
+
+    #[test]
+    fn map_handlers_converts_output() {
+        let router = Router::default()
+            .get("/foo", || String::from("foo"))
+            .get("/users/:id", |params: &Params| {
+                params.get("id").unwrap().to_string()
+            })
+            .case_insensitive(true);
+
+        let bytes: Router<Vec<u8>> = router.map_handlers(String::into_bytes);
+        assert_eq!(
+            b"foo".to_vec(),
+            bytes.resolve_full("GET", "/FOO").unwrap().body
+        );
+        assert_eq!(
+            b"42".to_vec(),
+            bytes.resolve_full("GET", "/users/42").unwrap().body
+        );
+        assert_eq!(
+            b"none".to_vec(),
+            bytes.resolve_or("GET", "/bar", || b"none".to_vec())
+        );
+
+        let bytes = bytes.get("/raw", || vec![0u8, 159]);
+        assert_eq!(
+            vec![0u8, 159],
+            bytes.resolve_full("GET", "/raw").unwrap().body
+        );
+    }
+
+    #[test]
+    fn routes_lists_every_route_in_order() {
+        let ok = || String::from("ok");
+        let router = Router::default()
+            .get("/", ok)
+            .get("/users/*", ok)
+            .post("/users/*", ok)
+            .name("create_user")
+            .any("/health/", ok)
+            .route_on_host("api.example.com", Method::DELETE, "/users/:id", ok);
+
+        assert_eq!((5, false), (router.len(), router.is_empty()));
+        assert!(Router::<String>::default().is_empty());
+        assert_eq!(
+            vec![
+                "GET /",
+                "GET /users/*",
+                "POST /users/*",
+                "ANY /health",
+                "DELETE api.example.com/users/:id",
+            ],
+            router
+                .routes()
+                .map(|node| node.to_string())
+                .collect::<Vec<_>>()
+        );
+        let node = router.routes().nth(2).unwrap();
+        assert_eq!(
+            (
+                &MethodMatcher::Only(Method::POST),
+                "/users/*",
+                Some("create_user"),
+                None
+            ),
+            (node.method(), node.pattern(), node.name(), node.host())
+        );
+        let matched = router.resolve_full("GET", "/users/1").unwrap();
+        assert_eq!(
+            "/users/*",
+            router.routes().nth(matched.index).unwrap().pattern()
+        );
+    }
+
+    #[test]
+    fn routes_for_method_filters_in_order() {
+        let router = Router::default()
+            .get("/b", || String::from("b"))
+            .post("/a", || String::from("a"))
+            .get("/a", || String::from("a"))
+            .get("/c/*", || String::from("c"));
+
+        let patterns: Vec<&str> = router
+            .routes_for_method(Method::GET)
+            .map(Node::pattern)
+            .collect();
+        assert_eq!(vec!["/b", "/a", "/c/*"], patterns);
+        assert_eq!(1, router.routes_for_method(Method::POST).count());
+        assert_eq!(0, router.routes_for_method(Method::DELETE).count());
+    }
+
+    #[test]
+    fn strict_requires_exact_consumption() {
+        let lenient = Router::default()
+            .get("/files/**", || String::from("files"))
+            .get("/a/*", || String::from("a"));
+        let strict = lenient.clone().strict(true);
+
+        assert_eq!("files", lenient.resolve("GET", "/files").unwrap());
+        assert_eq!(Err(RouteError::NotFound), strict.resolve("GET", "/files"));
+        assert_eq!(Err(RouteError::NotFound), strict.resolve("GET", "/files/"));
+
+        for router in [&lenient, &strict] {
+            assert_eq!("files", router.resolve("GET", "/files/a").unwrap());
+            assert_eq!("files", router.resolve("GET", "/files/a/b").unwrap());
+            assert_eq!("a", router.resolve("GET", "/a/x").unwrap());
+            assert_eq!(Err(RouteError::NotFound), router.resolve("GET", "/a"));
+            assert_eq!(Err(RouteError::NotFound), router.resolve("GET", "/a/x/y"));
+        }
+    }
+
+    #[test]
+    fn on_resolve_reports_each_resolve_once() {
+        use std::sync::Mutex;
+
+        static SEEN: Mutex<Vec<(String, Option<String>)>> = Mutex::new(vec![]);
+        fn record(info: &ResolveInfo) {
+            SEEN.lock().unwrap().push((
+                format!("{} {}", info.method, info.path),
+                info.pattern.map(String::from),
+            ));
+        }
+
+        let router = Router::default()
+            .get("/users/:id", || String::from("user"))
+            .on_resolve(record);
+
+        let _ = router.resolve("GET", "/users/1/");
+        let _ = router.resolve("GET", "/nope");
+        let _ = router.resolve_line("GET /users/2 HTTP/1.1");
+        let _ = router.resolve_full("POST", "/users/3");
+
+        assert_eq!(
+            vec![
+                (
+                    String::from("GET /users/1/"),
+                    Some(String::from("/users/:id"))
+                ),
+                (String::from("GET /nope"), None),
+                (
+                    String::from("GET /users/2"),
+                    Some(String::from("/users/:id"))
+                ),
+                (String::from("POST /users/3"), None),
+            ],
+            *SEEN.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn hooks_see_matches_and_misses() {
+        use std::sync::Mutex;
+
+        let seen = Arc::new(Mutex::new(vec![]));
+        let (first, second, missed) = (seen.clone(), seen.clone(), seen.clone());
+        let router = Router::default()
+            .get("/users/:id", || String::from("user"))
+            .on_match(move |info| {
+                first
+                    .lock()
+                    .unwrap()
+                    .push(format!("1 {} {} {}", info.method, info.path, info.pattern))
+            })
+            .on_match(move |info| second.lock().unwrap().push(format!("2 {}", info.pattern)))
+            .on_no_match(move |method, path| {
+                missed
+                    .lock()
+                    .unwrap()
+                    .push(format!("miss {} {}", method, path))
+            })
+            .or_else(Router::default().get("/about", || String::from("about")));
+
+        assert_eq!("user", router.resolve("GET", "/users//1/").unwrap());
+        assert_eq!("about", router.resolve("GET", "/about").unwrap());
+        assert!(router.resolve("GET", "/nope").is_err());
+        assert!(router.resolve("POST", "/about").is_err());
+        assert_eq!(
+            vec![
+                "1 GET /users/1 /users/:id",
+                "2 /users/:id",
+                "1 GET /about /about",
+                "2 /about",
+                "miss GET /nope",
+                "miss POST /about",
+            ],
+            *seen.lock().unwrap()
+        );
+
+        // a panicking hook panics the resolve, and the router works on
+        let router = Router::default()
+            .get("/", || String::from("home"))
+            .on_match(|_| panic!("hook"));
+        let caught =
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| router.resolve("GET", "/")));
+        assert!(caught.is_err());
+        assert_eq!(Err(RouteError::NotFound), router.resolve("GET", "/nope"));
+    }
+
+    #[test]
+    fn fallible_handlers_answer_through_the_error_handler() {
+        use std::io;
+
+        let router = Router::default()
+            .get_fallible("/users/:id", |params: &Params| match params.get("id") {
+                Some("1") => Ok(String::from("user 1")),
+                _ => Err(io::Error::new(io::ErrorKind::NotFound, "no such user")),
+            })
+            .get("/", || String::from("home"))
+            .error_handler(|error| {
+                let kind = error.downcast_ref::<io::Error>().map(io::Error::kind);
+                format!("{:?}: {}", kind, error)
+            });
+
+        assert_eq!("user 1", router.resolve("GET", "/users/1").unwrap());
+        assert_eq!(
+            "Some(NotFound): no such user",
+            router.resolve("GET", "/users/2").unwrap()
+        );
+        let failed = router.resolve_full("GET", "/users/2").unwrap();
+        assert_eq!(Some(String::from("no such user")), failed.error);
+        let home = router.resolve_full("GET", "/").unwrap();
+        assert_eq!(("home", None), (home.body.as_str(), home.error));
+        assert_eq!(
+            Err(RouteError::NotFound),
+            router.resolve_full("GET", "/nope")
+        );
+
+        // mapping keeps the error handler of the routes before it
+        let lengths = router.map_handlers(|body| body.len());
+        assert_eq!(Ok(28), lengths.resolve("GET", "/users/2"));
+
+        let router = Router::default().get_fallible("/", || Err::<String, _>("down"));
+        assert_eq!("internal server error", router.resolve("GET", "/").unwrap());
+    }
+
+    #[test]
+    fn optional_trailing_param() {
+        let router = Router::default()
+            .get("/posts/:id?", |params: &Params| match params.get("id") {
+                Some(id) => format!("post {}", id),
+                None => String::from("all posts"),
+            })
+            .get("/pages/:n:int?", |params: &Params| params.len().to_string())
+            .get("/posts/latest", || String::from("latest"));
+
+        assert_eq!("all posts", router.resolve("GET", "/posts").unwrap());
+        assert_eq!("all posts", router.resolve("GET", "/posts/").unwrap());
+        assert_eq!("post 42", router.resolve("GET", "/posts/42").unwrap());
+        assert_eq!("latest", router.resolve("GET", "/posts/latest").unwrap());
+        assert_eq!(
+            Err(RouteError::NotFound),
+            router.resolve("GET", "/posts/42/x")
+        );
+        assert!(router
+            .resolve_full("GET", "/posts")
+            .unwrap()
+            .params
+            .is_empty());
+
+        assert_eq!("0", router.resolve("GET", "/pages").unwrap());
+        assert_eq!("1", router.resolve("GET", "/pages/3").unwrap());
+        assert_eq!(
+            Err(RouteError::NotFound),
+            router.resolve("GET", "/pages/three")
+        );
+
+        assert_eq!("/", router.match_prefix(Method::GET, "/posts/").unwrap().1);
+        assert_eq!(
+            "/x",
+            router.match_prefix(Method::GET, "/posts/42/x").unwrap().1
+        );
+
+        // strict mode wants the optional segment present
+        let strict = router.strict(true);
+        assert_eq!(Err(RouteError::NotFound), strict.resolve("GET", "/posts"));
+        assert_eq!("post 42", strict.resolve("GET", "/posts/42").unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "optional params are only allowed as the last segment")]
+    fn optional_param_must_be_last() {
+        Router::default().get("/posts/:id?/comments", || String::from("comments"));
+    }
+
+    #[test]
+    fn several_optional_trailing_params() {
+        let router = Router::default()
+            .get("/archive/:year/[:month]/[:day]", |params: &Params| {
+                params
+                    .iter()
+                    .map(|(name, value)| format!("{}={}", name, value))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .name("archive")
+            .get("/articles/[:page:int]", |params: &Params| {
+                match params.get("page") {
+                    Some(page) => format!("page {}", page),
+                    None => String::from("first page"),
+                }
+            })
+            .get("/archive/:year/:month", || String::from("whole month"));
+
+        assert_eq!("year=2024", router.resolve("GET", "/archive/2024").unwrap());
+        assert_eq!(
+            "whole month",
+            router.resolve("GET", "/archive/2024/05").unwrap()
+        );
+        assert_eq!(
+            "year=2024 month=05 day=17",
+            router.resolve("GET", "/archive/2024/05/17").unwrap()
+        );
+        assert_eq!(Err(RouteError::NotFound), router.resolve("GET", "/archive"));
+        assert_eq!("first page", router.resolve("GET", "/articles").unwrap());
+        assert_eq!("page 2", router.resolve("GET", "/articles/2").unwrap());
+
+        for pattern in [
+            "/:year/[:month]/:day",
+            "/a/[:b]/c",
+            "/a/[b]",
+            "/a/[:b?]",
+            "/a/[:b",
+        ] {
+            assert!(Pattern::parse(pattern).is_err(), "{}", pattern);
         }
+        let error = Pattern::parse("/:year/[:month]/:day").unwrap_err();
+        assert_eq!(2, error.segment);
 
-        let mut nodes = self.nodes.clone();
-        nodes.push(Node::new(method, pattern, handler));
-        Self { nodes }
+        assert_eq!(
+            Ok(String::from("/archive/2024")),
+            router.url_for("archive", &["2024"])
+        );
+        assert_eq!(
+            Ok(String::from("/archive/2024/05/17")),
+            router.url_for("archive", &["2024", "05", "17"])
+        );
     }
 
-    pub fn get(&self, pattern: &str, handler: Handler) -> Self {
-        self.route(Method::GET, pattern, handler)
+    #[test]
+    fn route_if_registers_conditionally() {
+        for debug in [true, false] {
+            let router = Router::default()
+                .get("/", || String::from("home"))
+                .route_if(debug, Method::GET, "/debug", || String::from("debug"))
+                .get("/about", || String::from("about"));
+
+            assert_eq!(
+                if debug { 3 } else { 2 },
+                router.routes_for_method(Method::GET).count()
+            );
+            assert_eq!(debug, router.resolve("GET", "/debug").is_ok());
+            assert_eq!("about", router.resolve("GET", "/about").unwrap());
+        }
     }
-    pub fn post(&self, pattern: &str, handler: Handler) -> Self {
-        self.route(Method::POST, pattern, handler)
+
+    #[test]
+    fn malformed_paths_are_bad_requests() {
+        let router = Router::default().get("/files/:name", |params: &Params| {
+            params.get("name").unwrap().to_string()
+        });
+
+        assert_eq!("a b", router.resolve("GET", "/files/a%20b").unwrap());
+        for path in [
+            "/files/%zz",
+            "/files/50%",
+            "/files/%C3",
+            "/files/a%00",
+            "/files/a\0",
+        ] {
+            assert!(
+                matches!(
+                    router.resolve_full("GET", path),
+                    Err(RouteError::BadRequest(_))
+                ),
+                "{:?}",
+                path
+            );
+            assert!(matches!(
+                router.resolve("GET", path),
+                Err(RouteError::BadRequest(_))
+            ));
+        }
     }
-    pub fn put(&self, pattern: &str, handler: Handler) -> Self {
-        self.route(Method::PUT, pattern, handler)
+
+    #[test]
+    fn patterns_register_aliases() {
+        let router = Router::default()
+            .patterns(Method::GET, &["/", "/home", "/index"], || {
+                String::from("home")
+            })
+            .get("/about", || String::from("about"));
+
+        for path in ["/", "/home", "/index"] {
+            assert_eq!("home", router.resolve("GET", path).unwrap());
+        }
+        assert_eq!(
+            Err(RouteError::MethodNotAllowed(vec![Method::GET])),
+            router.resolve("POST", "/home")
+        );
+        assert_eq!(3, router.resolve_full("GET", "/about").unwrap().index);
     }
-    pub fn delete(&self, pattern: &str, handler: Handler) -> Self {
-        self.route(Method::DELETE, pattern, handler)
+
+    #[test]
+    fn other_methods_are_not_allowed() {
+        let router = Router::default()
+            .get("/users/:id", || String::from("show"))
+            .delete("/users/:id", || String::from("delete"))
+            .get("/users/:id", || String::from("again"))
+            .post("/users", || String::from("create"))
+            .route_on_host("admin.example.com", Method::PUT, "/users/:id", || {
+                String::from("update")
+            });
+
+        let error = router.resolve_full("PUT", "/users/1").unwrap_err();
+        assert_eq!(
+            RouteError::MethodNotAllowed(vec![Method::GET, Method::DELETE]),
+            error
+        );
+        assert_eq!(405, error.status());
+        assert_eq!(
+            "method not allowed, allowed: GET, DELETE",
+            error.to_string()
+        );
+        assert_eq!(
+            Err(RouteError::NotFound),
+            router.resolve_full("PUT", "/posts/1")
+        );
+        assert_eq!(404, RouteError::NotFound.status());
+        assert_eq!(Err(error), router.resolve("PUT", "/users/1"));
     }
 
-    pub fn resolve(&self, method: &str, path: &str) -> String {
-        for node in &self.nodes {
-            let method = Method::try_from(method).unwrap();
-            if node.method == method {
-                let path = {
-                    let mut a = path.to_string();
+    #[test]
+    fn failed_regex_falls_through() {
+        let router = Router::default()
+            .get("/img/:size(\\d+x\\d+)/:file", |params: &Params| {
+                format!("resized {}", params.get("size").unwrap())
+            })
+            .get("/img/:album/:file", |params: &Params| {
+                format!("album {}", params.get("album").unwrap())
+            });
 
-                    // remove consecutive slashes
-                    // /foo////bar -> /foo/bar
-                    while a.contains("//") {
-                        a = a.replace("//", "/");
-                    }
+        assert_eq!(
+            "resized 100x200",
+            router.resolve("GET", "/img/100x200/a.png").unwrap()
+        );
+        assert_eq!(
+            "album 100x",
+            router.resolve("GET", "/img/100x/a.png").unwrap()
+        );
+        assert_eq!(
+            "album cats",
+            router.resolve("GET", "/img/cats/a.png").unwrap()
+        );
+    }
 
-                    // trailing slash
-                    // /foo/ -> /foo
-                    if a.ends_with('/') {
-                        a.pop();
-                    }
+    #[test]
+    fn resolve_takes_a_method_or_a_string() {
+        let router = Router::default()
+            .get("/foo", || String::from("get foo"))
+            .post("/foo", || String::from("post foo"));
 
-                    a
-                };
+        assert_eq!("get foo", router.resolve(Method::GET, "/foo").unwrap());
+        assert_eq!("post foo", router.resolve("post", "/foo").unwrap());
+        assert_eq!(
+            "post foo",
+            router.resolve(String::from("POST"), "/foo").unwrap()
+        );
+        assert_eq!(Ok(Method::PATCH), "patch".parse());
+        assert_eq!(
+            Err(RouteError::InvalidMethod(String::from("G(E)T"))),
+            router.resolve("G(E)T", "/foo")
+        );
+        assert_eq!(
+            Err(RouteError::InvalidMethod(String::new())),
+            router.resolve_full(String::new(), "/foo")
+        );
+    }
 
-                // /foo/bar -> /foo/bar
-                // /foo/*/bar -> /foo/a/bar, /foo/b/bar, ...
-                let paths = path.split('/');
-                let node_paths = node.pattern.split('/');
-                if paths.clone().count() == node_paths.clone().count() {
-                    let ok = paths
-                        .zip(node_paths)
-                        .all(|(str, node_str)| str == node_str || node_str == "*");
-                    if ok {
-                        return (node.handler)();
-                    }
-                }
-            }
+    #[test]
+    fn resolve_method_skips_parsing() {
+        let router = Router::default()
+            .get("/foo", || String::from("get foo"))
+            .route(Method::Custom(String::from("PURGE")), "/foo", || {
+                String::from("purge foo")
+            })
+            .on_resolve(|info| assert!(info.method.eq_ignore_ascii_case("PURGE")));
+
+        assert_eq!(
+            "purge foo",
+            router
+                .resolve_method(Method::Custom(String::from("PURGE")), "/foo")
+                .unwrap()
+        );
+        assert_eq!(
+            Err(RouteError::NotFound),
+            router.resolve_method(Method::Custom(String::from("PURGE")), "/bar")
+        );
+        assert_eq!(
+            router.resolve("purge", "/foo"),
+            router.resolve_method(Method::Custom(String::from("PURGE")), "/foo")
+        );
+    }
+
+    #[test]
+    fn maintenance_short_circuits_every_route() {
+        let mut router = Router::default()
+            .get("/foo", || String::from("foo"))
+            .maintenance(|| String::from("503 maintenance"));
+
+        assert_eq!("503 maintenance", router.resolve("GET", "/foo").unwrap());
+        assert_eq!(
+            "503 maintenance",
+            router.resolve("POST", "/missing").unwrap()
+        );
+        assert_eq!(
+            "503 maintenance",
+            router.resolve_or("GET", "/missing", || String::from("fallback"))
+        );
+        assert_eq!(
+            Err(RouteError::Unavailable),
+            router.resolve_full("GET", "/foo")
+        );
+        assert_eq!(503, RouteError::Unavailable.status());
+
+        router.set_maintenance(false);
+        assert_eq!("foo", router.resolve("GET", "/foo").unwrap());
+        assert_eq!(Err(RouteError::NotFound), router.resolve("GET", "/missing"));
+
+        let mut router = Router::default().get("/foo", || String::from("foo"));
+        router.set_maintenance(true);
+        assert_eq!("foo", router.resolve("GET", "/foo").unwrap());
+    }
+
+    #[test]
+    fn exempt_routes_answer_during_maintenance() {
+        let router = Router::default()
+            .route_exempt(Method::GET, "/healthz", || String::from("ok"))
+            .get("/foo", || String::from("foo"))
+            .maintenance(|| String::from("503 maintenance"));
+
+        assert_eq!("ok", router.resolve("GET", "/healthz/").unwrap());
+        assert_eq!("503 maintenance", router.resolve("GET", "/foo").unwrap());
+        assert_eq!(
+            "503 maintenance",
+            router.resolve("POST", "/healthz").unwrap()
+        );
+        assert_eq!(
+            "503 maintenance",
+            router.resolve("GET", "/healthz%zz").unwrap()
+        );
+        assert_eq!("ok", router.resolve_full("GET", "/healthz").unwrap().body);
+    }
+
+    #[test]
+    fn exempt_routes_skip_middleware() {
+        let deny = |_: &dyn Fn(&Request) -> String, _: &Request| String::from("denied");
+        let router = Router::default()
+            .scope(
+                ScopeConfig {
+                    middleware: vec![Arc::new(deny)],
+                    ..Default::default()
+                },
+                |r| r.route_exempt(Method::GET, "/scoped", || String::from("ok")),
+            )
+            .append(
+                Router::default()
+                    .route_exempt(Method::GET, "/merged", || String::from("ok"))
+                    .middleware(deny),
+            )
+            .route_exempt(Method::GET, "/healthz", || String::from("ok"))
+            .get("/foo", || String::from("foo"))
+            .middleware(deny);
+
+        for path in ["/healthz", "/scoped", "/merged"] {
+            assert_eq!("ok", router.resolve("GET", path).unwrap());
         }
+        assert_eq!("denied", router.resolve("GET", "/foo").unwrap());
+        let lengths = router.map_handlers(|body| body.len());
+        assert_eq!(Ok(2), lengths.resolve("GET", "/healthz"));
+    }
+
+    #[test]
+    fn resolve_bytes_for_binary_bodies() {
+        let png = vec![0x89, b'P', b'N', b'G', 0xff];
+        let router = Router::default()
+            .get("/", || String::from("home"))
+            .map_handlers(String::into_bytes)
+            .get("/logo.png", move || png.clone());
 
-        String::from("no match routes")
+        assert_eq!(
+            vec![0x89, b'P', b'N', b'G', 0xff],
+            router.resolve_bytes("GET", "/logo.png")
+        );
+        assert_eq!(b"home".to_vec(), router.resolve_bytes("GET", "/"));
+        assert_eq!(
+            b"no match routes".to_vec(),
+            router.resolve_bytes("GET", "/missing")
+        );
     }
-}
 
-#[derive(new, Debug, Clone)]
-pub struct Node {
-    method: Method,
-    pattern: String,
-    handler: Handler,
-}
+    #[test]
+    fn scope_applies_prefix_host_and_middleware() {
+        let tag = |tag: &'static str| -> Middleware {
+            Arc::new(move |next, request| format!("{}({})", tag, next(request)))
+        };
+        let deny: Middleware = Arc::new(|next, request| match request.params.get("id") {
+            Some("0") => String::from("denied"),
+            _ => next(request),
+        });
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub enum Method {
-    GET,
-    POST,
-    PUT,
-    DELETE,
-}
+        let router = Router::default().get("/", || String::from("home")).scope(
+            ScopeConfig {
+                prefix: String::from("/admin/"),
+                host: Some(String::from("Admin.example.com")),
+                middleware: vec![tag("outer"), tag("second")],
+            },
+            |s| {
+                s.get("/", || String::from("dashboard"))
+                    .route_on_host("other.example.com", Method::GET, "/x", || String::from("x"))
+                    .scope(
+                        ScopeConfig {
+                            prefix: String::from("users"),
+                            middleware: vec![tag("inner"), deny],
+                            ..Default::default()
+                        },
+                        |s| {
+                            s.get("/:id", |params: &Params| {
+                                params.get("id").unwrap().to_string()
+                            })
+                        },
+                    )
+            },
+        );
 
-impl TryFrom<&str> for Method {
-    type Error = &'static str;
-    fn try_from(value: &str) -> Result<Self, Self::Error> {
-        match value {
-            "GET" | "get" => Ok(Method::GET),
-            "POST" | "post" => Ok(Method::POST),
-            "PUT" | "put" => Ok(Method::PUT),
-            "DELETE" | "delete" => Ok(Method::DELETE),
-            _ => Err("invalid method"),
+        let host = "admin.example.com";
+        assert_eq!("home", router.resolve("GET", "/").unwrap());
+        assert_eq!(
+            "outer(second(dashboard))",
+            router.resolve_with_host("GET", host, "/admin").unwrap()
+        );
+        assert_eq!(
+            "outer(second(inner(7)))",
+            router
+                .resolve_with_host("GET", host, "/admin/users/7")
+                .unwrap()
+        );
+        assert_eq!(
+            "outer(second(inner(denied)))",
+            router
+                .resolve_with_host("GET", host, "/admin/users/0")
+                .unwrap()
+        );
+        assert_eq!(
+            "outer(second(x))",
+            router
+                .resolve_with_host("GET", "other.example.com", "/admin/x")
+                .unwrap()
+        );
+        // scoped routes inherit the host
+        assert_eq!(Err(RouteError::NotFound), router.resolve("GET", "/admin"));
+        assert_eq!(
+            "/admin/users/:id",
+            router
+                .routes_for_method(Method::GET)
+                .last()
+                .unwrap()
+                .pattern()
+        );
+    }
+
+    #[test]
+    fn join_prefix_avoids_empty_segments() {
+        assert_eq!("/api/users", join_prefix("/api", "/users"));
+        assert_eq!("/api/users", join_prefix("/api/", "users"));
+        assert_eq!("/api", join_prefix("/api", "/"));
+        assert_eq!("/api", join_prefix("/api", ""));
+        assert_eq!("/users", join_prefix("", "/users"));
+        assert_eq!("/", join_prefix("", ""));
+    }
+
+    #[test]
+    fn equal_specificity_first_registered_wins() {
+        let x = || String::from("x");
+        let y = || String::from("y");
+
+        let router = Router::default().get("/a/:x", x).get("/a/:y", y);
+        let reversed = Router::default().get("/a/:y", y).get("/a/:x", x);
+        for _ in 0..10 {
+            assert_eq!("x", router.resolve("GET", "/a/1").unwrap());
+            assert_eq!("y", reversed.resolve("GET", "/a/1").unwrap());
         }
+        assert_eq!(
+            Some("1"),
+            router.resolve_full("GET", "/a/1").unwrap().params.get("x")
+        );
+        assert_eq!(1, router.conflicts().len());
+
+        // `*` and an unconstrained param rank the same
+        let router = Router::default()
+            .get("/a/*", || String::from("wildcard"))
+            .get("/a/:x", x);
+        assert_eq!("wildcard", router.resolve("GET", "/a/1").unwrap());
+        assert_eq!(
+            vec![(0, 1)],
+            router
+                .conflicts()
+                .iter()
+                .map(|conflict| (conflict.first, conflict.second))
+                .collect::<Vec<_>>()
+        );
     }
-}
 
-pub type Handler = fn() -> String;
+    #[cfg(feature = "stream")]
+    #[test]
+    fn resolve_stream_is_lazy() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        static PRODUCED: AtomicUsize = AtomicUsize::new(0);
+        let router = Router::default().get("/count/:n", |params: &Params| -> Stream {
+            let n: usize = params.get("n").unwrap().parse().unwrap();
+            Box::new((0..n).map(|i| {
+                PRODUCED.fetch_add(1, Ordering::SeqCst);
+                format!("{}\n", i)
+            }))
+        });
+
+        let mut stream = router.resolve_stream("GET", "/count/1000000");
+        assert_eq!(0, PRODUCED.load(Ordering::SeqCst));
+        assert_eq!(Some(String::from("0\n")), stream.next());
+        assert_eq!(Some(String::from("1\n")), stream.next());
+        assert_eq!(2, PRODUCED.load(Ordering::SeqCst));
+
+        let miss: Vec<String> = router.resolve_stream("GET", "/missing").collect();
+        assert_eq!(vec![String::from("no match routes")], miss);
+    }
 
     #[test]
-    fn route_adds_node() {
-        let router = Router::default();
-        assert_eq!(0, router.nodes.len());
+    fn validate_reports_every_warning() {
+        let ok = || String::from("ok");
+        let router = Router::default().get("/users/me", ok).get("/users/:id", ok);
+        assert_eq!(Ok(()), router.validate());
+
+        let router = router
+            .get("/users/:id", ok)
+            .get("/users/:name", ok)
+            .get("/a/:id:int", ok)
+            .get("/a/:id:enum(1|x)", ok);
+        let warnings = router.validate().unwrap_err();
+        let kinds: Vec<(&str, usize, usize)> = warnings
+            .iter()
+            .map(|warning| match warning {
+                RouterWarning::Duplicate(c) => ("duplicate", c.first, c.second),
+                RouterWarning::Unreachable(c) => ("unreachable", c.first, c.second),
+                RouterWarning::Conflict(c) => ("conflict", c.first, c.second),
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                ("duplicate", 1, 2),
+                ("unreachable", 1, 3),
+                ("unreachable", 2, 3),
+                ("conflict", 4, 5)
+            ],
+            kinds
+        );
+        assert_eq!(
+            "unreachable route #3 GET `/users/:name`, shadowed by #1 `/users/:id`",
+            warnings[1].to_string()
+        );
+    }
 
+    #[test]
+    fn or_else_tries_fallback_routers_in_order() {
+        let plugin = Router::default()
+            .get("/plugin", || String::from("plugin"))
+            .get("/shared", || String::from("plugin shared"))
+            .post("/form", || String::from("post form"));
+        let last = Router::default()
+            .get("/last", || String::from("last"))
+            .put("/form", || String::from("put form"));
         let router = Router::default()
-            .route(Method::GET, "/foo", || String::from("foo"))
-            .route(Method::GET, "/bar", || String::from("bar"));
-        assert_eq!(2, router.nodes.len())
+            .get("/", || String::from("home"))
+            .get("/shared", || String::from("home shared"))
+            .get("/form", || String::from("get form"))
+            .or_else(plugin)
+            .or_else(last);
+
+        assert_eq!("home shared", router.resolve("GET", "/shared").unwrap());
+        assert_eq!("plugin", router.resolve("GET", "/plugin").unwrap());
+        assert_eq!("last", router.resolve("GET", "/last").unwrap());
+        assert_eq!("put form", router.resolve("PUT", "/form").unwrap());
+        assert_eq!(3, router.resolve_full("GET", "/plugin").unwrap().index);
+        assert_eq!(6, router.resolve_full("GET", "/last").unwrap().index);
+
+        assert_eq!(
+            Err(RouteError::MethodNotAllowed(vec![
+                Method::GET,
+                Method::POST,
+                Method::PUT
+            ])),
+            router.resolve_full("DELETE", "/form")
+        );
+        assert_eq!(
+            Err(RouteError::MethodNotAllowed(vec![Method::GET])),
+            router.resolve_full("POST", "/last")
+        );
+        assert_eq!(
+            Err(RouteError::NotFound),
+            router.resolve_full("GET", "/nope")
+        );
+        assert_eq!(
+            "fallback",
+            router.resolve_or("GET", "/nope", || String::from("fallback"))
+        );
     }
 
     #[test]
-    fn resolve_returns_a_string() {
+    fn not_found_handler_sees_the_request() {
         let router = Router::default()
-            .route(Method::GET, "/get", || String::from("get"))
-            .route(Method::POST, "/post", || String::from("post"))
-            .put("/put", || String::from("put"))
-            .delete("/delete", || String::from("delete"));
+            .get("/", || String::from("home"))
+            .not_found(|request: &Request| format!("Page {} not found.", request.normalized_path()))
+            .method_not_allowed(|request: &Request| {
+                format!(
+                    "{} not allowed on {}",
+                    request.method.as_str(),
+                    request.path
+                )
+            });
 
-        assert_eq!("get", router.resolve("GET", "/get"));
-        assert_eq!("post", router.resolve("POST", "/post"));
-        assert_eq!("put", router.resolve("PUT", "/put"));
-        assert_eq!("delete", router.resolve("DELETE", "/delete"));
-        assert_eq!("no match routes", router.resolve("GET", "/foo"));
+        assert_eq!(
+            "Page /foo not found.",
+            router.resolve("GET", "/foo//").unwrap()
+        );
+        assert_eq!(
+            "Page /foo not found.",
+            router.resolve_or("GET", "/foo", || String::from("default"))
+        );
+        assert_eq!(
+            "POST not allowed on //",
+            router.resolve_method(Method::POST, "//").unwrap()
+        );
+        assert_eq!(
+            Err(RouteError::NotFound),
+            router.resolve_full("GET", "/foo")
+        );
+        // other errors aren't misses
+        assert!(matches!(
+            router.resolve("GET", "/%zz"),
+            Err(RouteError::BadRequest(_))
+        ));
+
+        // only the last handler in a chain fires
+        let chained = Router::default()
+            .not_found(|_: &Request| String::from("first"))
+            .or_else(router.clone())
+            .or_else(
+                Router::default().not_found(|request: &Request| format!("last {}", request.path)),
+            );
+        assert_eq!("last /foo", chained.resolve("GET", "/foo").unwrap());
+        assert_eq!("home", chained.resolve("GET", "/").unwrap());
     }
 
     #[test]
-    fn resolve_placeholder() {
+    fn outermost_not_found_handler_wins() {
+        let users = Router::default()
+            .get("/users", || String::from("users"))
+            .not_found(|_: &Request| String::from("users 404"));
         let router = Router::default()
-            .route(Method::GET, "/foo/*", || String::from("foo"))
-            .route(Method::GET, "/foo/*/*/bar", || String::from("foobar"));
+            .not_found(|_: &Request| String::from("replaced"))
+            .not_found(|request: &Request| format!("custom 404 for {}", request.path))
+            .merge(&users)
+            .scope("/admin", |admin| {
+                admin.not_found(|_: &Request| String::from("admin 404"))
+            });
 
-        assert_eq!("foo", router.resolve("GET", "/foo/1"));
-        assert_eq!("foo", router.resolve("GET", "/foo/a"));
-        assert_eq!("foobar", router.resolve("GET", "/foo/1/2/bar"));
-        assert_eq!("no match routes", router.resolve("GET", "/foo/1/2/3/bar"));
-        assert_eq!("no match routes", router.resolve("GET", "/foo/1/2/bar/3"));
+        assert_eq!("users", router.resolve("GET", "/users").unwrap());
+        assert_eq!(
+            "custom 404 for /users/1",
+            router.resolve("GET", "/users/1").unwrap()
+        );
+        assert_eq!(
+            "custom 404 for /admin/x",
+            router.resolve("GET", "/admin/x").unwrap()
+        );
     }
 
     #[test]
-    fn consecutive_slashes_ignored() {
-        let router = Router::default().route(Method::GET, "/a/b/c", || String::from("abc"));
+    fn named_params_by_name_and_position() {
+        let router = Router::default()
+            .get("/posts/:post_id/comments/:comment_id", |params: &Params| {
+                format!(
+                    "{} {} {:?}",
+                    params.get("post_id").unwrap(),
+                    params.get_index(1).unwrap(),
+                    params.get_index(2)
+                )
+            })
+            .get("/users/:id/", |params: &Params| {
+                format!("user {}", params.get("id").unwrap())
+            })
+            .get("/files/*/:name", |params: &Params| {
+                format!("{} of {}", params.get("name").unwrap(), params.len())
+            });
 
-        assert_eq!("abc", router.resolve("GET", "/a//////b//c"));
+        assert_eq!(
+            "7 9 None",
+            router.resolve("GET", "/posts/7/comments/9").unwrap()
+        );
+        assert_eq!("user 42", router.resolve("GET", "/users/42/").unwrap());
+        assert_eq!("user 42", router.resolve("GET", "/users/42").unwrap());
+        assert_eq!(
+            "a.txt of 1",
+            router.resolve("GET", "/files/x/a.txt").unwrap()
+        );
+        assert_eq!(
+            Err(RouteError::NotFound),
+            router.resolve("GET", "/posts/7/comments")
+        );
     }
 
     #[test]
-    fn trailing_slash() {
+    #[should_panic(expected = "duplicate param `id`")]
+    fn duplicate_param_names_are_rejected() {
+        Router::default().get("/users/:id/posts/:id", || String::from("x"));
+    }
+
+    #[test]
+    fn tail_matches_the_rest_of_the_path() {
         let router = Router::default()
-            .get("/foo", || String::from("foo"))
-            .get("/bar/", || String::from("bar"));
+            .get("/static/*filepath", |params: &Params| {
+                format!("file {}", params.get("filepath").unwrap())
+            })
+            .get("/static/favicon.ico", || String::from("favicon"))
+            .get("/static", || String::from("index"))
+            .get("/files/**", || String::from("any depth"));
+
+        assert_eq!(
+            "file a.css",
+            router.resolve("GET", "/static/a.css").unwrap()
+        );
+        assert_eq!(
+            "file a/b/c/d/e.js",
+            router.resolve("GET", "/static/a/b/c/d/e.js").unwrap()
+        );
+        assert_eq!(
+            "favicon",
+            router.resolve("GET", "/static/favicon.ico").unwrap()
+        );
+        assert_eq!("index", router.resolve("GET", "/static/").unwrap());
+        assert_eq!(
+            Err(RouteError::NotFound),
+            router.resolve("GET", "/assets/a.css")
+        );
+        assert_eq!(
+            "/static/*filepath",
+            router.resolve_full("GET", "/static/x").unwrap().pattern
+        );
+
+        // a tail is more specific than `**`
+        let router = Router::default()
+            .get("/a/**", || String::from("catch all"))
+            .get("/a/*rest", || String::from("tail"));
+        assert_eq!("tail", router.resolve("GET", "/a/b").unwrap());
+        assert_eq!("catch all", router.resolve("GET", "/a").unwrap());
+    }
+
+    #[test]
+    fn resolve_reports_misses_as_errors() {
+        let router = Router::default()
+            .get("/sentinel", || String::from("no match routes"))
+            .get("/foo", || String::from("foo"));
+
+        // a handler returning the old miss body is still a match
+        assert_eq!(
+            Ok(String::from("no match routes")),
+            router.resolve("GET", "/sentinel")
+        );
+        assert_eq!(Err(RouteError::NotFound), router.resolve("GET", "/bar"));
+        assert_eq!(
+            Err(RouteError::InvalidMethod(String::from("FO O"))),
+            router.resolve("FO O", "/foo")
+        );
+        let error: Box<dyn Error> = Box::new(RouteError::NotFound);
+        assert_eq!("no match routes", error.to_string());
+    }
+
+    #[test]
+    fn fn_items_and_capturing_closures_are_handlers() {
+        fn about() -> String {
+            String::from("about")
+        }
+        fn user(params: &Params) -> String {
+            format!("user {}", params.get("id").unwrap())
+        }
+
+        let greeting = String::from("hello from config");
+        let router: Router = Router::default()
+            .get("/", move || greeting.clone())
+            .get("/about", about)
+            .get("/users/:id", user)
+            .get("/x", || "x".into());
+
+        assert_eq!("hello from config", router.resolve("GET", "/").unwrap());
+        assert_eq!("about", router.resolve("GET", "/about").unwrap());
+        assert_eq!("user 1", router.resolve("GET", "/users/1").unwrap());
+        assert_eq!("x", router.resolve("GET", "/x").unwrap());
+        // handlers don't show up in `Debug`
+        let node = router.routes_for_method(Method::GET).nth(1).unwrap();
+        assert!(format!("{:?}", node).starts_with("Node { method: GET, pattern: Pattern"));
+    }
+
+    #[test]
+    fn handlers_see_the_request() {
+        let router = Router::default()
+            .get("/agent", |request: &Request| {
+                request.header("user-agent").unwrap_or("none").to_string()
+            })
+            .post("/echo/:id", |request: &Request| {
+                format!(
+                    "{} {} {}",
+                    request.path,
+                    request.params.get("id").unwrap(),
+                    request.body_str().unwrap_or("")
+                )
+            });
+
+        let request = Request::new(Method::GET, "/agent").with_header("User-Agent", "curl/8");
+        assert_eq!("curl/8", router.resolve_request(request).unwrap());
+        assert_eq!("none", router.resolve("GET", "/agent").unwrap());
+
+        let request = Request::new(Method::POST, "//echo/1/").with_body("hello");
+        assert_eq!(
+            "//echo/1/ 1 hello",
+            router.resolve_request(request).unwrap()
+        );
+        assert_eq!(
+            Err(RouteError::MethodNotAllowed(vec![Method::GET])),
+            router.resolve_request(Request::new(Method::PUT, "/agent"))
+        );
+    }
+
+    #[test]
+    fn head_falls_back_to_get() {
+        let router = Router::default()
+            .get("/users/:id", || String::from("get user"))
+            .get("/posts", || String::from("get posts"))
+            .head("/posts", || String::from("head posts"))
+            .post("/login", || String::from("login"));
+
+        assert_eq!("get user", router.resolve("HEAD", "/users/1").unwrap());
+        assert_eq!(
+            Method::GET,
+            router.resolve_full("HEAD", "/users/1").unwrap().method
+        );
+        assert_eq!("head posts", router.resolve("HEAD", "/posts").unwrap());
+        assert_eq!(
+            Err(RouteError::MethodNotAllowed(vec![Method::POST])),
+            router.resolve("HEAD", "/login")
+        );
+
+        let router = router.head_fallback(false);
+        assert_eq!(
+            Err(RouteError::MethodNotAllowed(vec![Method::GET])),
+            router.resolve("HEAD", "/users/1")
+        );
+        assert_eq!("head posts", router.resolve("HEAD", "/posts").unwrap());
+    }
+
+    #[test]
+    fn auto_options_lists_matching_methods() {
+        let router = Router::default()
+            .get("/files/*", || String::from("get file"))
+            .delete("/files/:name", || String::from("delete file"))
+            .post("/files/upload", || String::from("upload"))
+            .get("/files/upload", || String::from("upload form"))
+            .options("/admin", || String::from("admin options"))
+            .get("/admin", || String::from("admin"));
+
+        assert_eq!(
+            Err(RouteError::MethodNotAllowed(vec![
+                Method::GET,
+                Method::DELETE
+            ])),
+            router.resolve("OPTIONS", "/files/a")
+        );
+
+        let router = router.auto_options(true);
+        assert_eq!(
+            "GET, HEAD, DELETE",
+            router.resolve("OPTIONS", "/files/a").unwrap()
+        );
+        assert_eq!(
+            "GET, HEAD, DELETE, POST",
+            router.resolve("OPTIONS", "/files/upload").unwrap()
+        );
+        assert_eq!(
+            "GET, DELETE",
+            router
+                .clone()
+                .head_fallback(false)
+                .resolve("OPTIONS", "/files/a")
+                .unwrap()
+        );
+        assert_eq!(
+            "admin options",
+            router.resolve("OPTIONS", "/admin").unwrap()
+        );
+        assert_eq!(
+            Err(RouteError::NotFound),
+            router.resolve("OPTIONS", "/posts")
+        );
+        assert!(router.resolve_full("OPTIONS", "/files/a").is_err());
+        assert_eq!(
+            Err(RouteError::MethodNotAllowed(vec![
+                Method::GET,
+                Method::DELETE
+            ])),
+            router.resolve("PUT", "/files/a")
+        );
+    }
+
+    #[test]
+    fn middleware_wraps_matched_handlers_in_order() {
+        let log = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let tag = |tag: &'static str| {
+            let log = log.clone();
+            move |next: &dyn Fn(&Request) -> String, request: &Request| {
+                log.lock().unwrap().push(format!("{} in", tag));
+                let body = next(request);
+                log.lock().unwrap().push(format!("{} out", tag));
+                body
+            }
+        };
+        let handler_log = log.clone();
+        let router = Router::default()
+            .middleware(tag("a"))
+            .get("/", move || {
+                handler_log.lock().unwrap().push(String::from("handler"));
+                String::from("home")
+            })
+            .middleware(tag("b"))
+            .middleware(tag("c"));
+
+        assert_eq!("home", router.resolve("GET", "/").unwrap());
+        assert_eq!(
+            vec!["a in", "b in", "c in", "handler", "c out", "b out", "a out"],
+            *log.lock().unwrap()
+        );
+
+        log.lock().unwrap().clear();
+        assert_eq!(Err(RouteError::NotFound), router.resolve("GET", "/missing"));
+        assert!(log.lock().unwrap().is_empty());
+
+        let router = router.middleware(|next, request| match request.header("authorization") {
+            Some(_) => next(request),
+            None => String::from("unauthorized"),
+        });
+        assert_eq!("unauthorized", router.resolve("GET", "/").unwrap());
+        assert_eq!(
+            vec!["a in", "b in", "c in", "c out", "b out", "a out"],
+            *log.lock().unwrap()
+        );
+        let request = Request::new(Method::GET, "/").with_header("Authorization", "token");
+        assert_eq!("home", router.resolve_request(request).unwrap());
+    }
+
+    #[test]
+    fn nest_prefixes_a_built_router() {
+        let admin = Router::default()
+            .get("/", || String::from("admin"))
+            .get("/files/*", |params: &Params| {
+                format!("file {}", params.wildcard(0).unwrap())
+            })
+            .get("/logs/**", || String::from("logs"))
+            .middleware(|next, request| format!("[{}]", next(request)))
+            .not_found(|| String::from("admin 404"));
+        let app = Router::default()
+            .get("/", || String::from("home"))
+            .nest("/admin/", admin.clone())
+            .nest("/v1", Router::default().nest("/admin", admin));
+
+        assert_eq!(
+            vec![
+                "",
+                "/admin",
+                "/admin/files/*",
+                "/admin/logs/**",
+                "/v1/admin",
+                "/v1/admin/files/*",
+                "/v1/admin/logs/**"
+            ],
+            app.routes().map(Node::pattern).collect::<Vec<_>>()
+        );
+        assert_eq!("home", app.resolve("GET", "/").unwrap());
+        assert_eq!("[admin]", app.resolve("GET", "/admin").unwrap());
+        assert_eq!("[admin]", app.resolve("GET", "/admin/").unwrap());
+        assert_eq!("[file a]", app.resolve("GET", "/admin/files/a").unwrap());
+        assert_eq!("[logs]", app.resolve("GET", "/admin/logs/a/b").unwrap());
+        assert_eq!("[admin]", app.resolve("GET", "/v1/admin").unwrap());
+        assert_eq!("[file b]", app.resolve("GET", "/v1/admin/files/b").unwrap());
+        // the nested router's 404 handler stays behind
+        assert_eq!(
+            Err(RouteError::NotFound),
+            app.resolve("GET", "/admin/missing")
+        );
+    }
+
+    #[test]
+    fn scope_by_prefix_nests() {
+        let router = Router::default().scope("/api/", |api| {
+            api.get("/", || String::from("api")).scope("v1", |v1| {
+                v1.get("/users/", || String::from("users"))
+                    .get("/files/*", || String::from("file"))
+                    .get("/assets/**", || String::from("asset"))
+                    .get("/", || String::from("v1"))
+            })
+        });
+
+        assert_eq!(
+            vec![
+                "/api",
+                "/api/v1/users",
+                "/api/v1/files/*",
+                "/api/v1/assets/**",
+                "/api/v1"
+            ],
+            router.nodes.iter().map(Node::pattern).collect::<Vec<_>>()
+        );
+        assert_eq!("api", router.resolve("GET", "/api/").unwrap());
+        assert_eq!("v1", router.resolve("GET", "/api/v1").unwrap());
+        assert_eq!("users", router.resolve("GET", "/api/v1/users/").unwrap());
+        assert_eq!("file", router.resolve("GET", "/api/v1/files/a").unwrap());
+        assert_eq!(
+            "asset",
+            router.resolve("GET", "/api/v1/assets/a/b").unwrap()
+        );
+        assert_eq!(
+            Err(RouteError::NotFound),
+            router.resolve("GET", "/api/v1/files")
+        );
+        assert_eq!(
+            Err(RouteError::NotFound),
+            router.resolve("GET", "/v1/users")
+        );
+    }
+
+    #[test]
+    fn merge_appends_routes() {
+        let users = Router::default()
+            .get("/health", || String::from("users ok"))
+            .get("/users/:id", || String::from("user"))
+            .get("/files/*", || String::from("users file"));
+        let posts = Router::default()
+            .get("/health", || String::from("posts ok"))
+            .get("/posts", || String::from("posts"))
+            .get("/files/*", || String::from("posts file"))
+            .delete("/files/*", || String::from("delete file"))
+            .middleware(|next, request| format!("[{}]", next(request)));
+
+        let app = users.clone().merge(&posts);
+        assert_eq!(
+            vec![
+                "/health",
+                "/users/:id",
+                "/files/*",
+                "/health",
+                "/posts",
+                "/files/*",
+                "/files/*"
+            ],
+            app.nodes.iter().map(Node::pattern).collect::<Vec<_>>()
+        );
+        assert_eq!("users ok", app.resolve("GET", "/health").unwrap());
+        assert_eq!("user", app.resolve("GET", "/users/1").unwrap());
+        assert_eq!("[posts]", app.resolve("GET", "/posts").unwrap());
+        assert_eq!("users file", app.resolve("GET", "/files/a").unwrap());
+        assert_eq!("[delete file]", app.resolve("DELETE", "/files/a").unwrap());
+        assert!(matches!(
+            app.validate().unwrap_err()[..],
+            [
+                RouterWarning::Duplicate(Conflict {
+                    first: 0,
+                    second: 3,
+                    ..
+                }),
+                RouterWarning::Duplicate(Conflict {
+                    first: 2,
+                    second: 5,
+                    ..
+                })
+            ]
+        ));
+
+        let app = posts.append(users);
+        assert_eq!("[posts ok]", app.resolve("GET", "/health").unwrap());
+        assert_eq!("[user]", app.resolve("GET", "/users/1").unwrap());
+    }
+
+    #[test]
+    fn registering_many_routes_is_linear() {
+        let start = Instant::now();
+        let mut router = Router::default();
+        for i in 0..5000 {
+            router = router.get(&format!("/items/{}", i), move || i.to_string());
+        }
+        assert_eq!("4999", router.resolve("GET", "/items/4999").unwrap());
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn any_matches_every_method() {
+        let router = Router::default()
+            .any("/health", || String::from("ok"))
+            .any("/proxy/**", |request: &Request| {
+                format!("proxy {}", request.method)
+            })
+            .get("/proxy/**", || String::from("cached"));
+
+        for method in ["GET", "POST", "PUT", "DELETE", "PURGE"] {
+            assert_eq!("ok", router.resolve(method, "/health").unwrap());
+        }
+        assert_eq!("cached", router.resolve("GET", "/proxy/a").unwrap());
+        assert_eq!("proxy POST", router.resolve("POST", "/proxy/a").unwrap());
+        assert_eq!(
+            Method::PUT,
+            router.resolve_full("PUT", "/health").unwrap().method
+        );
+        assert_eq!(Err(RouteError::NotFound), router.resolve("GET", "/missing"));
+        assert!(router.conflicts().is_empty());
+        assert_eq!(
+            "duplicate route #1 ANY `/a`, shadowed by #0 `/a`",
+            Router::default()
+                .any("/a", String::new)
+                .any("/a", String::new)
+                .validate()
+                .unwrap_err()[0]
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn query_strings_are_not_part_of_the_path() {
+        let router = Router::default()
+            .get("/search", |request: &Request| {
+                let query = request.query_params();
+                format!(
+                    "{} page {}",
+                    query.get_all("q").collect::<Vec<_>>().join(","),
+                    query.get("page").unwrap_or("1")
+                )
+            })
+            .get("/users/:id", |params: &Params| {
+                params.get("id").unwrap().to_string()
+            });
+
+        assert_eq!(
+            "rust page 2",
+            router.resolve("GET", "/search?q=rust&page=2").unwrap()
+        );
+        assert_eq!(
+            "a b,c page 1",
+            router.resolve("GET", "/search/?q=a+b&q=c#results").unwrap()
+        );
+        assert_eq!("7", router.resolve("GET", "/users/7?x=1").unwrap());
+        assert_eq!("7", router.resolve("GET", "/users/7/?").unwrap());
+        assert_eq!(Err(RouteError::NotFound), router.resolve("GET", "/?q=1"));
+    }
+
+    #[test]
+    fn static_segments_match_decoded() {
+        let router = Router::default()
+            .get("/café/menu", || String::from("menu"))
+            .get("/a+b", || String::from("plus"));
+
+        assert_eq!("menu", router.resolve("GET", "/caf%C3%A9/menu").unwrap());
+        assert_eq!("menu", router.resolve("GET", "/café/menu").unwrap());
+        assert_eq!("plus", router.resolve("GET", "/a%2Bb").unwrap());
+        assert_eq!(
+            Err(RouteError::NotFound),
+            router.resolve("GET", "/caf%C3%A9%2Fmenu")
+        );
+    }
 
-        assert_eq!("foo", router.resolve("GET", "/foo"));
-        assert_eq!("foo", router.resolve("GET", "/foo/"));
-        assert_eq!("foo", router.resolve("GET", "/foo//"));
-        assert_eq!("bar", router.resolve("GET", "/bar"));
-        assert_eq!("bar", router.resolve("GET", "/bar/"));
-        assert_eq!("bar", router.resolve("GET", "/bar//"));
+    #[test]
+    fn case_insensitive_matching_only() {
+        let router = Router::default()
+            .get("/api/users/:name", |request: &Request| {
+                format!("{} {}", request.path, request.params.get("name").unwrap())
+            })
+            .get("/api/files/*", || String::from("file"));
+        assert_eq!(
+            Err(RouteError::NotFound),
+            router.resolve("GET", "/API/Users/Bob")
+        );
+
+        let router = router.case_insensitive(true);
+        assert_eq!(
+            "/API/Users/Bob/ Bob",
+            router.resolve("GET", "/API/Users/Bob/").unwrap()
+        );
+        assert_eq!("file", router.resolve("GET", "/Api/FILES/A.TXT").unwrap());
+        let matched = router.resolve_full("GET", "/api/USERS/bob").unwrap();
+        assert_eq!("/api/users/:name", matched.pattern);
+        assert_eq!(Some("bob"), matched.params.get("name"));
+    }
+}
+
+#[cfg(test)]
+mod fuzz {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn segment() -> impl Strategy<Value = String> {
+        prop_oneof![
+            "[a-c]{1,2}",
+            Just(String::from("*")),
+            Just(String::from(":p")),
+            Just(String::from(":n:int")),
+            Just(String::from(":n:int(0..10)")),
+            Just(String::from(":e:enum(a|b)")),
+        ]
+    }
+
+    fn pattern() -> impl Strategy<Value = String> {
+        (
+            prop::collection::vec(segment(), 0..5),
+            any::<bool>(),
+            any::<bool>(),
+        )
+            .prop_map(|(segments, catch_all, trailing)| {
+                // param names have to be unique, :p -> :p0
+                let mut segments: Vec<String> = segments
+                    .iter()
+                    .enumerate()
+                    .map(|(i, segment)| match segment.strip_prefix(':') {
+                        Some(param) => format!(":{}{}{}", &param[..1], i, &param[1..]),
+                        None => segment.clone(),
+                    })
+                    .collect();
+                if catch_all {
+                    segments.push(String::from("**"));
+                }
+                let mut pattern = format!("/{}", segments.join("/"));
+                if trailing && !segments.is_empty() {
+                    pattern.push('/');
+                }
+                pattern
+            })
+    }
+
+    fn path() -> impl Strategy<Value = String> {
+        prop_oneof!["[a-c0-9/]{0,16}", any::<String>()]
+    }
+
+    fn method() -> impl Strategy<Value = String> {
+        prop_oneof![
+            Just(String::from("GET")),
+            Just(String::from("post")),
+            "[A-Za-z]{1,8}",
+            any::<String>(),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn normalize_is_idempotent(path in path()) {
+            let once = normalize_path(&path);
+            prop_assert_eq!(normalize_path(&once), once.clone());
+            prop_assert!(!once.contains("//"));
+            prop_assert!(!once.ends_with('/'));
+        }
+
+        #[test]
+        fn index_keeps_every_matching_route(
+            patterns in prop::collection::vec(pattern(), 0..8),
+            path in "[a-cA-C0-9/]{0,12}",
+            case_insensitive in any::<bool>(),
+        ) {
+            let router = patterns
+                .iter()
+                .fold(Router::default(), |router, pattern| router.get(pattern, String::new))
+                .case_insensitive(case_insensitive);
+            let path = normalize_path(&path);
+            let scanned: Vec<usize> = router
+                .nodes
+                .iter()
+                .enumerate()
+                .filter(|(_, node)| node.pattern.match_path(&path, &router.options).is_some())
+                .map(|(index, _)| index)
+                .collect();
+            let indexed: Vec<usize> =
+                router.matching(None, None, &path).map(|(index, ..)| index).collect();
+            prop_assert_eq!(scanned, indexed);
+        }
+
+        #[test]
+        fn parse_pattern_never_panics(pattern in any::<String>()) {
+            let _ = Pattern::parse(&pattern);
+        }
+
+        #[test]
+        fn resolve_never_panics(
+            patterns in prop::collection::vec(pattern(), 0..8),
+            requests in prop::collection::vec((method(), path()), 1..16),
+        ) {
+            let router = patterns.iter().fold(Router::default(), |router, pattern| {
+                router
+                    .get(pattern, || String::from("get"))
+                    .route(Method::Custom(String::from("X")), pattern, || String::from("x"))
+            });
+            let cached = router.clone().with_cache(4);
+
+            for (method, path) in &requests {
+                let _ = router.resolve(method, path);
+                let _ = router.resolve_full(method, path);
+                let _ = router.resolve_line(&format!("{} {}", method, path));
+                let _ = router.resolve_with_host(method, path, path);
+                let _ = router.match_prefix(Method::GET, path);
+                for _ in 0..2 {
+                    prop_assert_eq!(
+                        router.resolve_full(method, path),
+                        cached.resolve_full(method, path)
+                    );
+                }
+                // only an origin-form target normalizes to the same one, any
+                // other is malformed or has a scheme normalizing would mangle
+                if path.starts_with('/') {
+                    prop_assert_eq!(
+                        router.resolve(method, path),
+                        router.resolve(method, &normalize_path(path))
+                    );
+                }
+            }
+            let _ = router.conflicts();
+        }
+
+        #[test]
+        fn literal_patterns_match_themselves(segments in prop::collection::vec("[a-c]{1,3}", 0..6)) {
+            let pattern = format!("/{}", segments.join("/"));
+            let router = Router::default().get(&pattern, || String::from("hit"));
+            prop_assert_eq!("hit", router.resolve("GET", &pattern).unwrap());
+            prop_assert_eq!("hit", router.resolve("GET", &format!("/{}/", segments.join("//"))).unwrap());
+        }
     }
 }
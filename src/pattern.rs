@@ -0,0 +1,1037 @@
+use crate::{normalize_path, Options, Params, UrlForError};
+use std::borrow::Cow;
+use std::error::Error;
+use std::fmt;
+
+// a parsed route pattern, usable on its own without a `Router`:
+// Pattern::parse("/users/:id")?.matches("/users/42") -> Some({id: 42})
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    raw: String,
+    segments: Vec<Segment>,
+    pub(crate) specificity: Vec<u8>,
+    // only with `Router::strict_slashes`, / has one
+    pub(crate) trailing_slash: bool,
+}
+
+impl Pattern {
+    // the pattern is checked whole: it starts with a `/`, has no empty
+    // segment but for a trailing slash, no whitespace or control character,
+    // and every segment parses. the error names the first segment that
+    // doesn't, 0 for the pattern as a whole.
+    pub fn parse(pattern: &str) -> Result<Pattern, PatternError> {
+        if !pattern.starts_with('/') {
+            return Err(PatternError {
+                pattern: pattern.to_string(),
+                segment: 0,
+                message: match pattern.is_empty() {
+                    true => String::from("empty pattern"),
+                    false => String::from("a pattern starts with `/`"),
+                },
+            });
+        }
+
+        // trailing slash
+        let mut raw = pattern.to_string();
+        let trailing_slash = raw.ends_with('/');
+        if trailing_slash {
+            raw.pop();
+        }
+
+        let segments = parse_segments(&raw)?;
+        let specificity = segments.iter().map(Segment::rank).collect();
+        Ok(Pattern {
+            raw,
+            segments,
+            specificity,
+            trailing_slash,
+        })
+    }
+
+    // the pattern as registered, after trailing slash trimming
+    pub fn as_str(&self) -> &str {
+        &self.raw
+    }
+
+    // normalizes `path` the same way `Router::resolve` does
+    pub fn matches(&self, path: &str) -> Option<Params> {
+        self.matches_normalized(&Pattern::normalize(path))
+    }
+
+    // `path` as `matches` normalizes it, to normalize once and match it
+    // against many patterns with `matches_normalized`
+    pub fn normalize(path: &str) -> String {
+        normalize_path(path)
+    }
+
+    // `matches` for a path that's already normalized
+    pub fn matches_normalized(&self, path: &str) -> Option<Params> {
+        self.match_path(path, &Options::default())
+    }
+
+    // /foo/bar -> /foo/bar
+    // /foo/*/bar -> /foo/a/bar, /foo/b/bar, ...
+    // /foo/*.json -> /foo/a.json, /foo/b.json, ...
+    // /foo/** -> /foo, /foo/a, /foo/a/b, ...
+    // /foo/**/bar -> /foo/bar, /foo/a/bar, /foo/a/b/bar, ...
+    pub(crate) fn match_path(&self, path: &str, options: &Options) -> Option<Params> {
+        let path = match options.strict_slashes {
+            true => {
+                let trimmed = path.strip_suffix('/');
+                if trimmed.is_some() != self.trailing_slash {
+                    return None;
+                }
+                trimmed.unwrap_or(path)
+            }
+            false => path,
+        };
+        let paths: Vec<&str> = path.split('/').collect();
+        let mut failed = vec![false; (self.segments.len() + 1) * (paths.len() + 1)];
+        let mut params = Params::default();
+        self.match_from(0, 0, &paths, options, &mut params, &mut failed)
+            .then_some(params)
+    }
+
+    // a `**` in the middle has to backtrack: it tries the shortest span first
+    // and grows it until the rest of the pattern fits. failed (segment, path
+    // position) pairs are remembered and never retried, so even /**/a/**/a/**/b
+    // is bounded by pattern segments × path segments² steps, not exponential.
+    fn match_from(
+        &self,
+        index: usize,
+        at: usize,
+        paths: &[&str],
+        options: &Options,
+        params: &mut Params,
+        failed: &mut [bool],
+    ) -> bool {
+        let state = index * (paths.len() + 1) + at;
+        if failed[state] {
+            return false;
+        }
+
+        let captured = params.len();
+        let wildcards = params.wildcards.len();
+        let matched = match self.segments.get(index) {
+            None => at == paths.len(),
+            // the rest, captured raw: /static/*path with /static/css/a%20b.css
+            // is `css/a%20b.css`
+            Some(Segment::Tail(name)) => {
+                let matched = at < paths.len();
+                if matched {
+                    params.push(name, &paths[at..].join("/"));
+                }
+                matched
+            }
+            // the spanned segments are captured raw, as a wildcard
+            Some(Segment::CatchAll) => {
+                let min = at + usize::from(options.strict);
+                let end = (min..=paths.len())
+                    .find(|&end| self.match_from(index + 1, end, paths, options, params, failed));
+                if let Some(end) = end {
+                    params.wildcards.insert(wildcards, paths[at..end].join("/"));
+                }
+                end.is_some()
+            }
+            Some(segment) if segment.is_optional() && at == paths.len() && !options.strict => {
+                self.match_from(index + 1, at, paths, options, params, failed)
+            }
+            // decoded after splitting, so /a%2Fb stays one segment, `a/b`
+            Some(segment) => match paths.get(at).map(|str| percent_decode(str)) {
+                Some(str) if segment.matches(&str, options) => {
+                    match segment {
+                        Segment::Param { name, .. } => params.push(name, &str),
+                        Segment::Wildcard => params.wildcards.push(str.into_owned()),
+                        Segment::Partial { prefix, suffix } => params
+                            .wildcards
+                            .push(str[prefix.len()..str.len() - suffix.len()].to_string()),
+                        _ => {}
+                    }
+                    self.match_from(index + 1, at + 1, paths, options, params, failed)
+                }
+                _ => false,
+            },
+        };
+
+        if !matched {
+            params.entries.truncate(captured);
+            params.wildcards.truncate(wildcards);
+            failed[state] = true;
+        }
+        matched
+    }
+
+    // the literal segments the pattern starts with, "" for the leading slash:
+    // /users/:id/posts -> "", "users"
+    pub(crate) fn static_prefix(&self) -> impl Iterator<Item = &str> {
+        self.segments.iter().map_while(|segment| match segment {
+            Segment::Static(node_str) => Some(node_str.as_str()),
+            _ => None,
+        })
+    }
+
+    // the pattern with a `{name}` for each param and wildcard, and the names
+    // in order. unnamed `*` and `**` are `param0`, `param1`, ... from the
+    // left: /users/*/posts/:post -> /users/{param0}/posts/{post}
+    #[cfg(feature = "serde")]
+    pub(crate) fn placeholders(&self) -> (String, Vec<String>) {
+        let mut unnamed = 0;
+        let mut names = vec![];
+        let segments: Vec<String> = self
+            .segments
+            .iter()
+            .map(|segment| {
+                let name = match segment {
+                    Segment::Static(str) => return str.clone(),
+                    Segment::Partial { prefix, suffix } => {
+                        unnamed += 1;
+                        let name = format!("param{}", unnamed - 1);
+                        let placeholder = format!("{}{{{}}}{}", prefix, name, suffix);
+                        names.push(name);
+                        return placeholder;
+                    }
+                    Segment::Param { name, .. } | Segment::Tail(name) => name.clone(),
+                    Segment::Wildcard | Segment::CatchAll => {
+                        unnamed += 1;
+                        format!("param{}", unnamed - 1)
+                    }
+                };
+                let placeholder = format!("{{{}}}", name);
+                names.push(name);
+                placeholder
+            })
+            .collect();
+        let path = match segments.join("/") {
+            path if path.is_empty() => String::from("/"),
+            path => path,
+        };
+        (path, names)
+    }
+
+    // every capture in order, as its name or `None` for an unnamed wildcard,
+    // and whether `match_from` keeps it raw (`*rest`, `**`) or decodes it
+    pub(crate) fn captures(&self) -> Vec<(Option<&str>, bool)> {
+        self.segments
+            .iter()
+            .filter_map(|segment| match segment {
+                Segment::Static(_) => None,
+                Segment::Param { name, .. } => Some((Some(name.as_str()), false)),
+                Segment::Tail(name) => Some((Some(name.as_str()), true)),
+                Segment::Wildcard | Segment::Partial { .. } => Some((None, false)),
+                Segment::CatchAll => Some((None, true)),
+            })
+            .collect()
+    }
+
+    // the path with `args` in place of params and wildcards, see
+    // `Router::url_for`
+    pub(crate) fn fill(&self, args: &[&str]) -> Result<String, UrlForError> {
+        let slots = self
+            .segments
+            .iter()
+            .filter(|segment| !matches!(segment, Segment::Static(_)))
+            .count();
+        let optional = self
+            .segments
+            .iter()
+            .rev()
+            .take_while(|segment| segment.is_optional())
+            .count();
+        if args.len() > slots || args.len() + optional < slots {
+            return Err(UrlForError::WrongArgumentCount {
+                expected: slots,
+                got: args.len(),
+            });
+        }
+
+        let mut args = args.iter().enumerate();
+        let mut url = vec![];
+        for segment in &self.segments {
+            let encoded = match segment {
+                Segment::Static(str) => str.clone(),
+                _ => match args.next() {
+                    None => break,
+                    Some((index, &"")) => {
+                        if matches!(segment, Segment::CatchAll) {
+                            continue;
+                        }
+                        return Err(UrlForError::EmptyArgument { index });
+                    }
+                    Some((_, arg)) => match segment {
+                        Segment::Tail(_) | Segment::CatchAll => arg
+                            .split('/')
+                            .map(percent_encode)
+                            .collect::<Vec<_>>()
+                            .join("/"),
+                        Segment::Partial { prefix, suffix } => {
+                            format!("{}{}{}", prefix, percent_encode(arg), suffix)
+                        }
+                        _ => percent_encode(arg),
+                    },
+                },
+            };
+            url.push(encoded);
+        }
+        Ok(url.join("/"))
+    }
+
+    // only called for patterns of equal specificity, so wildcards line up
+    pub(crate) fn overlaps(&self, other: &Pattern) -> bool {
+        self.segments
+            .iter()
+            .zip(&other.segments)
+            .all(|pair| match pair {
+                (Segment::Static(a), Segment::Static(b)) => a == b,
+                (
+                    Segment::Partial {
+                        prefix: a_prefix,
+                        suffix: a_suffix,
+                    },
+                    Segment::Partial {
+                        prefix: b_prefix,
+                        suffix: b_suffix,
+                    },
+                ) => {
+                    (a_prefix.starts_with(b_prefix.as_str())
+                        || b_prefix.starts_with(a_prefix.as_str()))
+                        && (a_suffix.ends_with(b_suffix.as_str())
+                            || b_suffix.ends_with(a_suffix.as_str()))
+                }
+                _ => true,
+            })
+    }
+
+    // whether `self` matches every path `other` does, for patterns of equal
+    // specificity. params compare by constraint, not by name.
+    pub(crate) fn covers(&self, other: &Pattern) -> bool {
+        self.segments.len() == other.segments.len()
+            && self
+                .segments
+                .iter()
+                .zip(&other.segments)
+                .all(|pair| match pair {
+                    (
+                        Segment::Param {
+                            constraint: a,
+                            optional: a_optional,
+                            ..
+                        },
+                        Segment::Param {
+                            constraint: b,
+                            optional: b_optional,
+                            ..
+                        },
+                    ) => a == b && a_optional == b_optional,
+                    (Segment::Tail(_), Segment::Tail(_)) => true,
+                    (
+                        Segment::Wildcard,
+                        Segment::Param {
+                            constraint: None, ..
+                        },
+                    )
+                    | (
+                        Segment::Param {
+                            constraint: None, ..
+                        },
+                        Segment::Wildcard,
+                    ) => true,
+                    (a, b) => a == b,
+                })
+    }
+
+    pub(crate) fn match_prefix<'a>(&self, path: &'a str, options: &Options) -> Option<&'a str> {
+        let mut rest = path;
+        for (i, segment) in self.segments.iter().enumerate() {
+            match segment {
+                Segment::Static(node_str) if node_str.is_empty() => continue,
+                Segment::Tail(_) => break,
+                // by position, every `**` equals every other
+                Segment::CatchAll if i + 1 == self.segments.len() => break,
+                // a `**` in the middle can't end a prefix
+                Segment::CatchAll => return None,
+                _ => {}
+            }
+
+            let trimmed = rest.trim_start_matches('/');
+            if trimmed.is_empty() && segment.is_optional() {
+                break;
+            }
+            let end = trimmed.find('/').unwrap_or(trimmed.len());
+            let str = percent_decode(&trimmed[..end]);
+            if str.is_empty() || !segment.matches(&str, options) {
+                return None;
+            }
+            rest = &trimmed[end..];
+        }
+        Some(rest)
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.raw)
+    }
+}
+
+// escapes everything but the characters a segment may hold as is, the
+// reverse of `percent_decode`
+pub(crate) fn percent_encode(str: &str) -> String {
+    let mut encoded = String::new();
+    for byte in str.bytes() {
+        match byte {
+            b'A'..=b'Z'
+            | b'a'..=b'z'
+            | b'0'..=b'9'
+            | b'-'
+            | b'.'
+            | b'_'
+            | b'~'
+            | b'!'
+            | b'$'
+            | b'&'
+            | b'\''
+            | b'('
+            | b')'
+            | b'*'
+            | b'+'
+            | b','
+            | b';'
+            | b'='
+            | b':'
+            | b'@' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+// decodes `%xx` escapes in a single path segment. a segment with a malformed
+// escape, or that doesn't decode to UTF-8, is kept as-is.
+pub(crate) fn percent_decode(segment: &str) -> Cow<'_, str> {
+    try_percent_decode(segment).unwrap_or(Cow::Borrowed(segment))
+}
+
+fn try_percent_decode(segment: &str) -> Result<Cow<'_, str>, &'static str> {
+    if !segment.contains('%') {
+        return Ok(Cow::Borrowed(segment));
+    }
+
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let byte = bytes
+                .get(i + 1..i + 3)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or("malformed percent-encoding")?;
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    String::from_utf8(decoded)
+        .map(Cow::Owned)
+        .map_err(|_| "percent-encoded path is not UTF-8")
+}
+
+// a request path the router refuses to match, see `RouteError::BadRequest`
+pub(crate) fn check_path(path: &str) -> Result<(), &'static str> {
+    for segment in path.split('/') {
+        if segment.chars().any(char::is_control) {
+            return Err("control character in path");
+        }
+        if try_percent_decode(segment)?.chars().any(char::is_control) {
+            return Err("percent-encoded control character in path");
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatternError {
+    pub pattern: String,
+    pub segment: usize,
+    pub message: String,
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid pattern `{}` at segment {}: {}",
+            self.pattern, self.segment, self.message
+        )
+    }
+}
+
+impl Error for PatternError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Static(String),
+    // *
+    Wildcard,
+    // a single `*` inside a segment: *.json, report-*, v*-beta. the middle
+    // has to be at least one character and is captured like a `*`. a suffix
+    // starting like a name makes a `*name` instead, `*json` is a tail.
+    Partial {
+        prefix: String,
+        suffix: String,
+    },
+    // :name, :name:int, :name:int(1..100), :name:enum(a|b), :name(\d+x\d+)
+    // and with a `?` suffix or in brackets, optional if only optional
+    // segments follow: :name?, [:name]
+    // or in braces, {name} and {name:\d+x\d+}
+    Param {
+        name: String,
+        constraint: Option<Constraint>,
+        optional: bool,
+    },
+    // *rest, one or more segments, only as the last segment
+    Tail(String),
+    // **, zero or more segments, anywhere in the pattern
+    CatchAll,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Constraint {
+    // inclusive bounds
+    Int { min: Option<i64>, max: Option<i64> },
+    Enum(Vec<String>),
+    // anchored to the whole segment
+    Regex(Regex),
+}
+
+// compared by source, `regex::Regex` has no `PartialEq`
+#[derive(Debug, Clone)]
+struct Regex(regex::Regex);
+
+impl PartialEq for Regex {
+    fn eq(&self, other: &Regex) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+impl Eq for Regex {}
+
+impl Segment {
+    // compared segment by segment from the left, lower wins:
+    // /users/me > /users/*.json > /users/:id:int > /users/:id > /users/:id?
+    // > /users/*rest > /users/**
+    fn rank(&self) -> u8 {
+        match self {
+            Segment::Static(_) => 0,
+            Segment::Partial { .. } => 1,
+            Segment::Param { optional: true, .. } => 4,
+            Segment::Param {
+                constraint: Some(_),
+                ..
+            } => 2,
+            Segment::Wildcard | Segment::Param { .. } => 3,
+            Segment::Tail(_) => 5,
+            Segment::CatchAll => 6,
+        }
+    }
+
+    fn is_optional(&self) -> bool {
+        matches!(self, Segment::Param { optional: true, .. })
+    }
+
+    // whether a single path segment fits
+    fn matches(&self, str: &str, options: &Options) -> bool {
+        match self {
+            Segment::Static(node_str) => eq_static(node_str, str, options),
+            Segment::Wildcard | Segment::Tail(_) | Segment::CatchAll => true,
+            // compared, not searched: the prefix and the suffix at either end
+            Segment::Partial { prefix, suffix } => {
+                let end = str.len().wrapping_sub(suffix.len());
+                str.len() > prefix.len() + suffix.len()
+                    && str
+                        .get(..prefix.len())
+                        .is_some_and(|start| eq_static(prefix, start, options))
+                    && str
+                        .get(end..)
+                        .is_some_and(|end| eq_static(suffix, end, options))
+            }
+            Segment::Param { constraint, .. } => match constraint {
+                None => true,
+                Some(Constraint::Int { min, max }) => match str.parse::<i64>() {
+                    Ok(n) => min.is_none_or(|min| n >= min) && max.is_none_or(|max| n <= max),
+                    Err(_) => false,
+                },
+                Some(Constraint::Enum(choices)) => choices.iter().any(|choice| choice == str),
+                Some(Constraint::Regex(regex)) => regex.0.is_match(str),
+            },
+        }
+    }
+}
+
+pub(crate) fn eq_static(node_str: &str, str: &str, options: &Options) -> bool {
+    if !options.case_insensitive {
+        return node_str == str;
+    }
+
+    #[cfg(feature = "unicode")]
+    return unicase::UniCase::new(node_str) == unicase::UniCase::new(str);
+    #[cfg(not(feature = "unicode"))]
+    return node_str.eq_ignore_ascii_case(str);
+}
+
+fn parse_segments(pattern: &str) -> Result<Vec<Segment>, PatternError> {
+    let node_strs: Vec<&str> = pattern.split('/').collect();
+    let segments: Vec<Segment> = node_strs
+        .iter()
+        .enumerate()
+        .map(|(i, node_str)| {
+            let error = |message: &str| PatternError {
+                pattern: pattern.to_string(),
+                segment: i,
+                message: message.to_string(),
+            };
+
+            if node_str
+                .chars()
+                .any(|c| c.is_whitespace() || c.is_control())
+            {
+                return Err(error("whitespace or control character in segment"));
+            }
+            match *node_str {
+                // /a//b, the leading "" is the root
+                "" if i > 0 => Err(error("empty segment")),
+                // requests have theirs resolved first, see `normalize`
+                "." | ".." => Err(error("`.` and `..` segments never match")),
+                "*" => Ok(Segment::Wildcard),
+                "**" => Ok(Segment::CatchAll),
+                node_str if is_tail(node_str) => match i + 1 == node_strs.len() {
+                    true => Ok(Segment::Tail(node_str[1..].to_string())),
+                    false => Err(error("`*name` is only allowed as the last segment")),
+                },
+                node_str if node_str.starts_with('{') => {
+                    parse_braced(node_str).map_err(|message| error(&message))
+                }
+                // [:page] is :page?
+                node_str if node_str.starts_with('[') => {
+                    match node_str
+                        .strip_prefix("[:")
+                        .and_then(|p| p.strip_suffix(']'))
+                    {
+                        Some(param) if !param.ends_with('?') => {
+                            parse_param(&format!("{}?", param)).map_err(|message| error(&message))
+                        }
+                        _ => Err(error(&format!("expected `[:name]`, got `{}`", node_str))),
+                    }
+                }
+                node_str => match node_str.strip_prefix(':') {
+                    Some(param) => parse_param(param).map_err(|message| error(&message)),
+                    None => match node_str.split_once('*') {
+                        Some((_, suffix)) if suffix.contains('*') => {
+                            Err(error("only one `*` is allowed in a segment"))
+                        }
+                        Some((prefix, suffix)) => Ok(Segment::Partial {
+                            prefix: prefix.to_string(),
+                            suffix: suffix.to_string(),
+                        }),
+                        None => Ok(Segment::Static(node_str.to_string())),
+                    },
+                },
+            }
+        })
+        .collect::<Result<_, _>>()?;
+
+    // only trailing segments can be left out: /:year/:month?/:day? but not
+    // /:year?/:month
+    if let Some(i) = segments
+        .windows(2)
+        .position(|pair| pair[0].is_optional() && !pair[1].is_optional())
+    {
+        return Err(PatternError {
+            pattern: pattern.to_string(),
+            segment: i,
+            message: String::from("optional params are only allowed as the last segments"),
+        });
+    }
+
+    // each name binds one value: /a/:id/b/:id is rejected
+    let mut names = vec![];
+    for (i, segment) in segments.iter().enumerate() {
+        if let Segment::Param { name, .. } | Segment::Tail(name) = segment {
+            if names.contains(&name) {
+                return Err(PatternError {
+                    pattern: pattern.to_string(),
+                    segment: i,
+                    message: format!("duplicate param `{}`", name),
+                });
+            }
+            names.push(name);
+        }
+    }
+    Ok(segments)
+}
+
+// *rest, *file_path. the name is an identifier
+fn is_tail(node_str: &str) -> bool {
+    let mut name = match node_str.strip_prefix('*') {
+        Some(name) => name.chars(),
+        None => return false,
+    };
+    name.next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+// id, id:int, id:int(1..100), id:int(1..=100), status:enum(active|archived), id?
+// and size(\d+x\d+), a regex right after the name. it can't contain a `/`.
+fn parse_param(param: &str) -> Result<Segment, String> {
+    let (param, optional) = match param.strip_suffix('?') {
+        Some(param) => (param, true),
+        None => (param, false),
+    };
+    let regex_start = param.find('(').filter(|&i| !param[..i].contains(':'));
+    let (name, constraint) = match (regex_start, param.split_once(':')) {
+        (Some(i), _) => (&param[..i], Some(parse_regex(&param[i..])?)),
+        (None, Some((name, constraint))) => (name, Some(parse_constraint(constraint)?)),
+        (None, None) => (param, None),
+    };
+    if name.is_empty() {
+        return Err(String::from("missing param name"));
+    }
+
+    Ok(Segment::Param {
+        name: name.to_string(),
+        constraint,
+        optional,
+    })
+}
+
+// {id}, {id:\d+}, {year:[0-9]{4}}: everything after the first `:` is the
+// regex, braces and all
+fn parse_braced(node_str: &str) -> Result<Segment, String> {
+    let param = node_str
+        .strip_prefix('{')
+        .and_then(|param| param.strip_suffix('}'))
+        .ok_or_else(|| format!("unclosed `{{` in `{}`", node_str))?;
+    let (name, constraint) = match param.split_once(':') {
+        Some((name, regex)) => (name, Some(parse_regex(&format!("({})", regex))?)),
+        None => (param, None),
+    };
+    if name.is_empty() {
+        return Err(String::from("missing param name"));
+    }
+
+    Ok(Segment::Param {
+        name: name.to_string(),
+        constraint,
+        optional: false,
+    })
+}
+
+fn parse_constraint(constraint: &str) -> Result<Constraint, String> {
+    let (kind, args) = match constraint.split_once('(') {
+        Some((kind, rest)) => match rest.strip_suffix(')') {
+            Some(args) => (kind, Some(args)),
+            None => return Err(format!("unclosed `(` in `{}`", constraint)),
+        },
+        None => (constraint, None),
+    };
+
+    match (kind, args) {
+        ("int", None) => Ok(Constraint::Int {
+            min: None,
+            max: None,
+        }),
+        ("int", Some(range)) => parse_range(range),
+        ("enum", Some(choices)) => {
+            let choices: Vec<String> = choices.split('|').map(String::from).collect();
+            if choices.iter().any(String::is_empty) {
+                return Err(format!("empty choice in `{}`", constraint));
+            }
+            Ok(Constraint::Enum(choices))
+        }
+        _ => Err(format!("unknown constraint `{}`", constraint)),
+    }
+}
+
+fn parse_regex(regex: &str) -> Result<Constraint, String> {
+    let source = regex
+        .strip_prefix('(')
+        .and_then(|regex| regex.strip_suffix(')'))
+        .ok_or_else(|| format!("unclosed `(` in `{}`", regex))?;
+    regex::Regex::new(&format!("^(?:{})$", source))
+        .map(|regex| Constraint::Regex(Regex(regex)))
+        .map_err(|error| format!("invalid regex `{}`: {}", source, error))
+}
+
+// 1..100 (exclusive), 1..=100, 1.., ..100
+fn parse_range(range: &str) -> Result<Constraint, String> {
+    let invalid = || format!("invalid range `{}`", range);
+    let bound = |n: &str| -> Result<Option<i64>, String> {
+        match n {
+            "" => Ok(None),
+            n => n.parse().map(Some).map_err(|_| invalid()),
+        }
+    };
+
+    let (start, end) = range.split_once("..").ok_or_else(invalid)?;
+    let min = bound(start)?;
+    let max = match end.strip_prefix('=') {
+        Some(end) => bound(end)?.ok_or_else(invalid)?.into(),
+        None => bound(end)?.map(|end| end - 1),
+    };
+    if let (Some(min), Some(max)) = (min, max) {
+        if min > max {
+            return Err(format!("empty range `{}`", range));
+        }
+    }
+
+    Ok(Constraint::Int { min, max })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pattern_matches_without_router() {
+        let pattern = Pattern::parse("/users/:id:int/posts/*/").unwrap();
+        assert_eq!("/users/:id:int/posts/*", pattern.as_str());
+
+        let params = pattern.matches("/users/42/posts/hello").unwrap();
+        assert_eq!(Some("42"), params.get("id"));
+        assert_eq!(1, params.len());
+        assert!(pattern.matches("//users/42/posts//hello/").is_some());
+        let path = Pattern::normalize("//users/42/posts//hello/");
+        assert_eq!("/users/42/posts/hello", path);
+        assert_eq!(Some(params), pattern.matches_normalized(&path));
+
+        assert!(pattern.matches("/users/abc/posts/hello").is_none());
+        assert!(pattern.matches("/users/42/posts").is_none());
+        assert!(pattern.matches("/users/42/posts/a/b").is_none());
+
+        let pattern = Pattern::parse("/").unwrap();
+        assert!(pattern.matches("/").is_some());
+        assert!(pattern.matches("").is_some());
+        assert!(pattern.matches("/a").is_none());
+    }
+
+    #[test]
+    fn invalid_constraints_are_rejected() {
+        for pattern in [
+            "/a/:id:float",
+            "/a/:id:int(",
+            "/a/:id:int(x..5)",
+            "/a/:id:int(5..1)",
+            "/a/:id:int(1..=)",
+            "/a/:id:enum()",
+            "/a/:id:enum(a||b)",
+            "/a/:",
+            "/a/:id?/b",
+            "/a/:?",
+            "/a/:id(\\d+",
+            "/a/:id([0-9)",
+            "/a/:(\\d+)",
+            "/a/:id/b/:id",
+            "/a/:id/:id:int",
+        ] {
+            assert!(Pattern::parse(pattern).is_err(), "{}", pattern);
+        }
+
+        let error = Pattern::parse("/a/:id/b/:id").unwrap_err();
+        assert_eq!(4, error.segment);
+        assert_eq!("duplicate param `id`", error.message);
+
+        let error = Pattern::parse("/a/:id:float").unwrap_err();
+        assert_eq!(2, error.segment);
+        assert_eq!(
+            "invalid pattern `/a/:id:float` at segment 2: unknown constraint `float`",
+            error.to_string()
+        );
+    }
+
+    #[test]
+    fn percent_decode_segments() {
+        assert_eq!("a/b", percent_decode("a%2Fb"));
+        assert_eq!("a/b", percent_decode("a%2fb"));
+        assert_eq!("my report.txt", percent_decode("my%20report.txt"));
+        assert_eq!("café", percent_decode("caf%C3%A9"));
+        assert_eq!("plain", percent_decode("plain"));
+
+        // malformed escapes and non-UTF-8 results are left alone
+        assert_eq!("100%", percent_decode("100%"));
+        assert_eq!("%zz", percent_decode("%zz"));
+        assert_eq!("%4", percent_decode("%4"));
+        assert_eq!("%FF", percent_decode("%FF"));
+        assert_eq!("%é", percent_decode("%é"));
+        assert_eq!("a%20b%", percent_decode("a%20b%"));
+    }
+
+    #[test]
+    fn check_path_rejects_undecodable_paths() {
+        assert_eq!(Ok(()), check_path("/a%20b/caf%C3%A9/a%2Fb"));
+        assert_eq!(Err("malformed percent-encoding"), check_path("/a/%zz"));
+        assert_eq!(Err("malformed percent-encoding"), check_path("/a/100%"));
+        assert_eq!(Err("percent-encoded path is not UTF-8"), check_path("/%FF"));
+        assert_eq!(Err("control character in path"), check_path("/a\0b"));
+        assert_eq!(Err("control character in path"), check_path("/a\nb"));
+        assert_eq!(
+            Err("percent-encoded control character in path"),
+            check_path("/a%00b")
+        );
+    }
+
+    #[test]
+    fn regex_constraints() {
+        let pattern = Pattern::parse("/img/:size(\\d+x\\d+)/:file").unwrap();
+        let params = pattern.matches("/img/100x200/cat.png").unwrap();
+        assert_eq!(Some("100x200"), params.get("size"));
+        assert_eq!(Some("cat.png"), params.get("file"));
+        // anchored to the whole segment
+        assert!(pattern.matches("/img/100x200px/cat.png").is_none());
+        assert!(pattern.matches("/img/x100x200/cat.png").is_none());
+        assert!(pattern.matches("/img/large/cat.png").is_none());
+
+        let pattern = Pattern::parse("/v/:version(v1|v2)?").unwrap();
+        assert!(pattern.matches("/v/v2").is_some());
+        assert!(pattern.matches("/v").is_some());
+        assert!(pattern.matches("/v/v3").is_none());
+
+        let error = Pattern::parse("/a/:id([0-9)").unwrap_err();
+        assert!(error.message.starts_with("invalid regex `[0-9`"));
+    }
+
+    #[test]
+    fn braced_params() {
+        let pattern = Pattern::parse("/posts/{year:[0-9]{4}}/{slug}").unwrap();
+        let params = pattern.matches("/posts/2024/hello").unwrap();
+        assert_eq!(Some("2024"), params.get("year"));
+        assert_eq!(Some("hello"), params.get("slug"));
+        assert!(pattern.matches("/posts/24/hello").is_none());
+
+        let pattern = Pattern::parse("/users/{id:\\d+}").unwrap();
+        assert!(pattern.matches("/users/12").is_some());
+        assert!(pattern.matches("/users/12abc").is_none());
+        assert!(pattern.matches("/users/avatar.png").is_none());
+
+        for pattern in ["/a/{id", "/a/{}", "/a/{:\\d+}", "/a/{id:[0-9}"] {
+            assert!(Pattern::parse(pattern).is_err(), "{}", pattern);
+        }
+        let error = Pattern::parse("/a/{id:(}").unwrap_err();
+        assert_eq!(2, error.segment);
+        assert!(error.message.starts_with("invalid regex `(`"));
+    }
+
+    #[test]
+    fn named_tail() {
+        let pattern = Pattern::parse("/static/*filepath").unwrap();
+        for path in ["css/site.css", "js/vendor/a/b/foo.js", "my%20file"] {
+            let params = pattern.matches(&format!("/static/{}", path)).unwrap();
+            assert_eq!(Some(path), params.get("filepath"));
+        }
+        // one or more segments, so no empty remainder
+        assert!(pattern.matches("/static/").is_none());
+        assert!(pattern.matches("/static").is_none());
+
+        for pattern in ["/a/*rest/b", "/a/*rest/*rest", "/a/:rest/*rest"] {
+            assert!(Pattern::parse(pattern).is_err(), "{}", pattern);
+        }
+        let error = Pattern::parse("/a/*rest/b").unwrap_err();
+        assert_eq!("`*name` is only allowed as the last segment", error.message);
+    }
+
+    #[test]
+    fn catch_all_in_the_middle() {
+        let pattern = Pattern::parse("/a/**/b").unwrap();
+        for (path, span) in [
+            ("/a/b", ""),
+            ("/a/x/b", "x"),
+            ("/a/x/y/b", "x/y"),
+            ("/a/x/y/z/b", "x/y/z"),
+            ("/a/b/b", "b"),
+        ] {
+            let params = pattern.matches(path).unwrap();
+            assert_eq!(Some(span), params.wildcard(0), "{}", path);
+        }
+        assert!(pattern.matches("/a").is_none());
+        assert!(pattern.matches("/a/x/y").is_none());
+        assert!(pattern.matches("/a/x/b/c").is_none());
+
+        let strict = Options {
+            strict: true,
+            ..Options::default()
+        };
+        assert!(pattern.match_path("/a/b", &strict).is_none());
+        assert!(pattern.match_path("/a/x/b", &strict).is_some());
+
+        let pattern = Pattern::parse("/**/:id:int/**").unwrap();
+        let params = pattern.matches("/x/y/42/z").unwrap();
+        assert_eq!(vec![("id", "42")], params.iter().collect::<Vec<_>>());
+        assert_eq!(vec!["x/y", "z"], params.wildcards().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn wildcards_are_not_counted_as_params() {
+        for pattern in ["/a/*/:id", "/a/**/:id"] {
+            let params = Pattern::parse(pattern).unwrap().matches("/a/b/1").unwrap();
+            assert_eq!(1, params.len(), "{}", pattern);
+            assert_eq!(Some("1"), params.get_index(0), "{}", pattern);
+            assert_eq!(Some("b"), params.wildcard(0), "{}", pattern);
+        }
+    }
+
+    #[test]
+    fn catch_all_backtracking_is_bounded() {
+        let pattern = Pattern::parse("/**/a/**/a/**/a/**/b").unwrap();
+        let path = "/a".repeat(2000);
+        assert!(pattern.matches(&path).is_none());
+        assert!(pattern.matches(&format!("{}/b", path)).is_some());
+    }
+
+    #[test]
+    fn partial_wildcards() {
+        for (pattern, path, middle) in [
+            ("/data/*.json", "/data/stats.json", "stats"),
+            ("/reports/report-*", "/reports/report-2024", "2024"),
+            ("/v/v*-beta", "/v/v2-beta", "2"),
+            ("/data/*.json", "/data/a%20b.json", "a b"),
+        ] {
+            let params = Pattern::parse(pattern).unwrap().matches(path).unwrap();
+            assert_eq!(Some(middle), params.wildcard(0), "{}", path);
+            assert_eq!(0, params.len());
+        }
+
+        let pattern = Pattern::parse("/data/*.json").unwrap();
+        assert!(pattern.matches("/data/stats.csv").is_none());
+        assert!(pattern.matches("/data/stats.json.csv").is_none());
+        // the middle can't be empty
+        assert!(pattern.matches("/data/.json").is_none());
+        let pattern = Pattern::parse("/v/v*-beta").unwrap();
+        assert!(pattern.matches("/v/v-beta").is_none());
+        assert!(pattern.matches("/v/x2-beta").is_none());
+
+        assert_eq!(
+            Ok(String::from("/data/a%20b.json")),
+            Pattern::parse("/data/*.json").unwrap().fill(&["a b"])
+        );
+        let error = Pattern::parse("/a/*-*").unwrap_err();
+        assert_eq!(2, error.segment);
+        assert_eq!("only one `*` is allowed in a segment", error.message);
+    }
+
+    #[test]
+    fn encoded_slash_stays_in_segment() {
+        let pattern = Pattern::parse("/files/:name").unwrap();
+        assert_eq!(
+            Some("a/b"),
+            pattern.matches("/files/a%2Fb").unwrap().get("name")
+        );
+        assert!(pattern.matches("/files/a/b").is_none());
+
+        let pattern = Pattern::parse("/files/a/b").unwrap();
+        assert!(pattern.matches("/files/a%2Fb").is_none());
+        assert!(pattern.matches("/files/%61/b").is_some());
+    }
+}
@@ -0,0 +1,230 @@
+use crate::pattern::percent_decode;
+use crate::{normalize_path, Method, Params};
+
+// what a handler sees of the request being resolved:
+// router.resolve_request(Request::new(Method::POST, "/echo").with_body("hi"))
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Request {
+    pub method: Method,
+    // as sent, before normalization, without the query
+    pub path: String,
+    // raw, without the `?`, see `query_params`
+    pub query: Option<String>,
+    pub body: Option<Vec<u8>>,
+    // captured by the matched route, empty until then
+    pub params: Params,
+    headers: Vec<(String, String)>,
+}
+
+impl Request {
+    // a query in `path` is split off into `query` and a `#fragment` dropped:
+    // /search?q=rust#top -> /search and q=rust
+    pub fn new(method: Method, path: &str) -> Self {
+        let path = path.split_once('#').map_or(path, |(path, _)| path);
+        let (path, query) = match path.split_once('?') {
+            Some((path, query)) => (path, Some(query.to_string())),
+            None => (path, None),
+        };
+        Request {
+            method,
+            path: path.to_string(),
+            query,
+            body: None,
+            params: Params::default(),
+            headers: vec![],
+        }
+    }
+
+    pub fn with_query(mut self, query: &str) -> Self {
+        self.query = Some(query.to_string());
+        self
+    }
+
+    // repeated headers are kept, `header` returns the first
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn with_body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = Some(body.into());
+        self
+    }
+
+    // header names compare case-insensitively, `content-type` finds
+    // `Content-Type`
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn headers(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.headers
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+
+    // the decoded query, empty without one
+    pub fn query_params(&self) -> Query {
+        Query::parse(self.query.as_deref().unwrap_or(""))
+    }
+
+    // the body as text, `None` without a body or if it isn't UTF-8
+    pub fn body_str(&self) -> Option<&str> {
+        std::str::from_utf8(self.body.as_deref()?).ok()
+    }
+
+    // `path` the way routes are matched against it, /foo//bar/ -> /foo/bar
+    pub fn normalized_path(&self) -> String {
+        normalize_path(&self.path)
+    }
+}
+
+// a request-target as sent, split into its authority and the path to
+// route on:
+// origin-form /a -> (None, /a)
+// absolute-form http://example.com:8080/a -> (Some(example.com:8080), /a),
+// with / for an empty path and any user info dropped
+// asterisk-form * -> (None, *), only for `OPTIONS *`
+// an empty target is taken for /, anything else, like a relative path, is
+// malformed.
+pub(crate) fn split_target(target: &str) -> Result<(Option<&str>, &str), &'static str> {
+    if target.starts_with('/') || target == "*" || target.is_empty() {
+        return Ok((None, target));
+    }
+    let malformed = Err("malformed request-target");
+    let Some((scheme, rest)) = target.split_once("://") else {
+        return malformed;
+    };
+    let mut chars = scheme.chars();
+    if !chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        || !chars.all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+    {
+        return malformed;
+    }
+    let (authority, path) = rest.split_at(rest.find(['/', '?']).unwrap_or(rest.len()));
+    let authority = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host);
+    if authority.is_empty() {
+        return malformed;
+    }
+    match path.starts_with('/') {
+        true => Ok((Some(authority), path)),
+        false => Ok((Some(authority), "/")),
+    }
+}
+
+// `a=1&tag=x&tag=y&debug`, decoded, in order. a key without `=` has an
+// empty value.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Query {
+    entries: Vec<(String, String)>,
+}
+
+impl Query {
+    pub fn parse(query: &str) -> Self {
+        let entries = query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+                (decode(key), decode(value))
+            })
+            .collect();
+        Query { entries }
+    }
+
+    // the first value for `key`
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    // every value for a repeated key, ?tag=a&tag=b
+    pub fn get_all<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a str> {
+        self.entries
+            .iter()
+            .filter(move |(name, _)| name == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+}
+
+// `+` is a space in a query, `%2B` a plus
+fn decode(str: &str) -> String {
+    percent_decode(&str.replace('+', " ")).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn query_is_split_off_and_parsed() {
+        let request = Request::new(
+            Method::GET,
+            "/search/?q=rust+lang&tag=a&tag=b%2Bc&debug#top",
+        );
+        assert_eq!("/search/", request.path);
+        assert_eq!(
+            Some("q=rust+lang&tag=a&tag=b%2Bc&debug"),
+            request.query.as_deref()
+        );
+
+        let query = request.query_params();
+        assert_eq!(Some("rust lang"), query.get("q"));
+        assert_eq!(vec!["a", "b+c"], query.get_all("tag").collect::<Vec<_>>());
+        assert_eq!(Some(""), query.get("debug"));
+        assert!(!query.contains("page"));
+        assert_eq!(4, query.iter().count());
+
+        let request = Request::new(Method::GET, "/a#frag?not=query");
+        assert_eq!(("/a", None), (request.path.as_str(), request.query));
+        assert_eq!(
+            Query::default(),
+            Request::new(Method::GET, "/a?").query_params()
+        );
+        assert_eq!(Some("%zz"), Query::parse("bad=%zz").get("bad"));
+    }
+
+    #[test]
+    fn request_targets_are_split() {
+        assert_eq!(Ok((None, "/a/b")), split_target("/a/b"));
+        assert_eq!(Ok((None, "*")), split_target("*"));
+        assert_eq!(
+            Ok((Some("example.com"), "/a")),
+            split_target("http://example.com/a")
+        );
+        assert_eq!(
+            Ok((Some("example.com:8080"), "/")),
+            split_target("https://user:pw@example.com:8080")
+        );
+        assert_eq!(
+            Ok((Some("[::1]:80"), "/")),
+            split_target("http://[::1]:80?q=1")
+        );
+        assert_eq!(Ok((None, "")), split_target(""));
+        for target in ["a/b", "://nope", "http://", "http:///a", "1http://a/", "**"] {
+            assert_eq!(
+                Err("malformed request-target"),
+                split_target(target),
+                "{:?}",
+                target
+            );
+        }
+    }
+}
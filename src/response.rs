@@ -0,0 +1,378 @@
+use crate::pattern::percent_encode;
+use crate::{Body, Params, Pattern, RouteError, Router};
+use std::sync::Arc;
+
+// what a `Router<Response>` answers with. handlers return one directly or
+// build it from text, `String` and `&str` become a 200 `text/plain`:
+// router.get("/", || Response::json("{}"))
+// any other bytes are `Response::bytes`.
+// a string router converts with `map_handlers(Response::from)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    pub status: u16,
+    pub body: Body,
+    headers: Vec<(String, String)>,
+}
+
+impl Response {
+    pub fn ok(body: impl Into<Body>) -> Self {
+        Response::status(200)
+            .with_header("Content-Type", "text/plain")
+            .with_body(body)
+    }
+
+    pub fn json(body: impl Into<Body>) -> Self {
+        Response::status(200)
+            .with_header("Content-Type", "application/json")
+            .with_body(body)
+    }
+
+    // `application/octet-stream`, for a body that isn't text
+    pub fn bytes(body: impl Into<Body>) -> Self {
+        Response::status(200)
+            .with_header("Content-Type", "application/octet-stream")
+            .with_body(body)
+    }
+
+    // an empty body without a `Content-Type`
+    pub fn status(status: u16) -> Self {
+        Response {
+            status,
+            body: Body::default(),
+            headers: vec![],
+        }
+    }
+
+    pub fn with_status(mut self, status: u16) -> Self {
+        self.status = status;
+        self
+    }
+
+    pub fn with_body(mut self, body: impl Into<Body>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    // replaces any header of the same name, compared case-insensitively
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers
+            .retain(|(key, _)| !key.eq_ignore_ascii_case(name));
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn headers(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.headers
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+}
+
+impl From<String> for Response {
+    fn from(body: String) -> Self {
+        Response::ok(body)
+    }
+}
+
+impl From<&str> for Response {
+    fn from(body: &str) -> Self {
+        Response::ok(body)
+    }
+}
+
+impl From<Vec<u8>> for Response {
+    fn from(body: Vec<u8>) -> Self {
+        Response::bytes(body)
+    }
+}
+
+impl From<Body> for Response {
+    fn from(body: Body) -> Self {
+        match body.to_str() {
+            Some(_) => Response::ok(body),
+            None => Response::bytes(body),
+        }
+    }
+}
+
+// the error's status with its message as the body; a 405 lists the
+// allowed methods in `Allow`
+impl From<RouteError> for Response {
+    fn from(error: RouteError) -> Self {
+        let response = Response::ok(error.to_string()).with_status(error.status());
+        match &error {
+            RouteError::MethodNotAllowed(allowed) => {
+                let allowed: Vec<&str> = allowed.iter().map(|method| method.as_str()).collect();
+                response.with_header("Allow", &allowed.join(", "))
+            }
+            _ => response,
+        }
+    }
+}
+
+impl<S: Clone> Router<Response, S> {
+    // like `resolve`, a miss is a real response too: a 404, 405, ...
+    // unless a `not_found` or `method_not_allowed` handler answers it
+    pub fn resolve_response(&self, method: &str, path: &str) -> Response {
+        self.resolve(method, path).unwrap_or_else(Response::from)
+    }
+
+    // GET and HEAD routes answering with a 302 to `to`. the target is sent
+    // as written, but for segments referring to what `from` captured: `*` or
+    // `**` for its unnamed wildcards in order, `:name` or `*name` for a
+    // named one. router.redirect("/old/*", "/new/*")
+    pub fn redirect(self, from: &str, to: &str) -> Self {
+        self.redirect_with(302, from, to)
+    }
+
+    // like `redirect`, with a 301 clients may remember
+    pub fn redirect_permanent(self, from: &str, to: &str) -> Self {
+        self.redirect_with(301, from, to)
+    }
+
+    fn redirect_with(self, status: u16, from: &str, to: &str) -> Self {
+        let pattern = Pattern::parse(from).unwrap_or_else(|e| panic!("{}", e));
+        let target = Arc::new(Target::parse(&pattern, to));
+        let handler = move |params: &Params| {
+            Response::status(status).with_header("Location", &target.fill(params))
+        };
+        self.get(from, handler.clone()).head(from, handler)
+    }
+}
+
+// a redirect target, split at its back-references
+struct Target {
+    parts: Vec<Part>,
+    // the query and fragment, always verbatim
+    suffix: String,
+}
+
+enum Part {
+    Verbatim(String),
+    // the `index`th unnamed wildcard
+    Wildcard { index: usize, raw: bool },
+    Named { name: String, raw: bool },
+}
+
+impl Target {
+    // panics on a reference to a capture `pattern` doesn't have
+    fn parse(pattern: &Pattern, to: &str) -> Target {
+        let captures = pattern.captures();
+        let mut unnamed = captures.iter().filter(|(name, _)| name.is_none());
+        let mut index = 0;
+        let (path, suffix) = to.split_at(to.find(['?', '#']).unwrap_or(to.len()));
+        let parts = path
+            .split('/')
+            .map(|segment| match segment {
+                "*" | "**" => {
+                    let Some(&(_, raw)) = unnamed.next() else {
+                        panic!("`{}` has more wildcards than `{}`", to, pattern);
+                    };
+                    index += 1;
+                    Part::Wildcard {
+                        index: index - 1,
+                        raw,
+                    }
+                }
+                _ if segment.len() > 1 && segment.starts_with([':', '*']) => {
+                    let name = &segment[1..];
+                    let Some(&(_, raw)) = captures.iter().find(|(other, _)| *other == Some(name))
+                    else {
+                        panic!("`{}` has no param `{}`", pattern, name);
+                    };
+                    Part::Named {
+                        name: name.to_string(),
+                        raw,
+                    }
+                }
+                _ => Part::Verbatim(segment.to_string()),
+            })
+            .collect();
+        Target {
+            parts,
+            suffix: suffix.to_string(),
+        }
+    }
+
+    // decoded captures are encoded again, raw ones are already. a capture
+    // that matched nothing, an optional param or an empty `**`, drops its
+    // segment: /archive/**/ is /archive/, not /archive//.
+    fn fill(&self, params: &Params) -> String {
+        let parts: Vec<String> = self
+            .parts
+            .iter()
+            .filter_map(|part| {
+                let (value, raw) = match part {
+                    Part::Verbatim(str) => return Some(str.clone()),
+                    Part::Wildcard { index, raw } => (params.wildcard(*index), *raw),
+                    Part::Named { name, raw } => (params.get(name), *raw),
+                };
+                match (value.unwrap_or_default(), raw) {
+                    ("", _) => None,
+                    (value, true) => Some(value.to_string()),
+                    (value, false) => Some(percent_encode(value)),
+                }
+            })
+            .collect();
+        match parts.join("/") {
+            path if path.is_empty() => String::from("/") + &self.suffix,
+            path => path + &self.suffix,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Params;
+
+    #[test]
+    fn text_converts_to_a_plain_200() {
+        for response in [Response::from("hi"), Response::from(String::from("hi"))] {
+            assert_eq!(200, response.status);
+            assert_eq!("hi", response.body);
+            assert_eq!(Some("text/plain"), response.header("content-type"));
+        }
+
+        let response = Response::status(204);
+        assert_eq!((204, true), (response.status, response.body.is_empty()));
+        assert_eq!(0, response.headers().count());
+        assert_eq!(
+            Some("application/json"),
+            Response::json("{}").header("Content-Type")
+        );
+    }
+
+    #[test]
+    fn custom_headers_replace_defaults() {
+        let response = Response::ok("<p>hi</p>")
+            .with_header("content-type", "text/html")
+            .with_header("Cache-Control", "no-store");
+        assert_eq!(Some("text/html"), response.header("Content-Type"));
+        assert_eq!(Some("no-store"), response.header("cache-control"));
+        assert_eq!(2, response.headers().count());
+    }
+
+    #[test]
+    fn misses_are_real_responses() {
+        let router = Router::default().get("/", || Response::json("[]")).get(
+            "/users/:id",
+            |params: &Params| {
+                Response::from(format!("user {}", params.get("id").unwrap()))
+                    .with_header("X-User", params.get("id").unwrap())
+            },
+        );
+        assert_eq!(Response::json("[]"), router.resolve_response("GET", "/"));
+        let response = router.resolve_response("GET", "/users/7");
+        assert_eq!(
+            (Some("user 7"), Some("7")),
+            (response.body.to_str(), response.header("x-user"))
+        );
+
+        let response = router.resolve_response("GET", "/missing");
+        assert_eq!(
+            (404, Some("no match routes")),
+            (response.status, response.body.to_str())
+        );
+        let response = router.resolve_response("POST", "/");
+        assert_eq!(
+            (405, Some("GET")),
+            (response.status, response.header("Allow"))
+        );
+
+        let router = Router::default()
+            .get("/", || String::from("home"))
+            .map_handlers(Response::from);
+        assert_eq!(Response::ok("home"), router.resolve_response("GET", "/"));
+    }
+
+    #[test]
+    fn redirects_with_back_references() {
+        let router = Router::default()
+            .redirect("/", "/dashboard")
+            .redirect_permanent("/old-docs", "/docs/")
+            .redirect("/old/*/edit", "/new/*")
+            .redirect("/files/**", "/archive/**/")
+            .redirect_permanent("/u/:id/*rest", "https://example.com/users/:id/*rest?from=u")
+            .head_fallback(false);
+        let location = |method, path| {
+            let response = router.resolve_response(method, path);
+            (
+                response.status,
+                response.header("Location").map(String::from),
+            )
+        };
+
+        assert_eq!(
+            (302, Some(String::from("/dashboard"))),
+            location("GET", "/")
+        );
+        assert_eq!(
+            (302, Some(String::from("/dashboard"))),
+            location("HEAD", "/")
+        );
+        assert_eq!(
+            (301, Some(String::from("/docs/"))),
+            location("GET", "/old-docs")
+        );
+        assert_eq!(405, location("POST", "/old-docs").0);
+        assert_eq!(
+            (302, Some(String::from("/new/a%20b"))),
+            location("GET", "/old/a%20b/edit")
+        );
+        assert_eq!(
+            Some(String::from("/archive/x/y%20z/")),
+            location("GET", "/files/x/y%20z").1
+        );
+        assert_eq!(Some(String::from("/archive/")), location("GET", "/files").1);
+        assert_eq!(
+            Some(String::from("https://example.com/users/7/a/b?from=u")),
+            location("GET", "/u/7/a/b").1
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "`/old` has no param `id`")]
+    fn redirect_to_an_unknown_param_panics() {
+        Router::<Response>::default().redirect("/old", "/new/:id");
+    }
+
+    #[test]
+    fn binary_bodies_round_trip() {
+        let png = vec![0u8, 159, 146, 150];
+        let router = Router::default()
+            .get("/logo", {
+                let png = png.clone();
+                move || Body::from(png.clone())
+            })
+            .get("/hello", || Body::from("hello"));
+        assert_eq!(png, router.resolve("GET", "/logo").unwrap());
+        assert_eq!(None, router.resolve("GET", "/logo").unwrap().to_str());
+        assert_eq!("hello", router.resolve("GET", "/hello").unwrap());
+
+        let router = router.map_handlers(Response::from);
+        let response = router.resolve_response("GET", "/logo");
+        assert_eq!(png.as_slice(), response.body.as_bytes());
+        assert_eq!(
+            Some("application/octet-stream"),
+            response.header("Content-Type")
+        );
+        assert_eq!(
+            Some("text/plain"),
+            router
+                .resolve_response("GET", "/hello")
+                .header("Content-Type")
+        );
+        assert_eq!(
+            "[0, 159, 146, 150]",
+            format!("{:?}", Body::from(png.as_slice()))
+        );
+    }
+}
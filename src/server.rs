@@ -0,0 +1,221 @@
+// a bare HTTP/1.1 server for prototypes, with the `server` feature:
+// Router::default().get("/", || String::from("hi")).listen("127.0.0.1:8080")
+// one thread per connection, one request per connection, no TLS.
+use crate::{Method, Request, Response, RouteError, Router};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::thread;
+use std::time::Duration;
+
+// longer request lines and headers are a 400, a longer body a 413
+const MAX_LINE: u64 = 8 * 1024;
+const MAX_HEADERS: usize = 100;
+const MAX_BODY: usize = 1024 * 1024;
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+impl<T: Into<Response>, S: Clone> Router<T, S>
+where
+    Self: Sync,
+{
+    pub fn listen(&self, addr: impl ToSocketAddrs) -> io::Result<()> {
+        self.serve(TcpListener::bind(addr)?)
+    }
+
+    // like `listen`, on a bound listener, e.g. one on port 0 in a test.
+    // only returns if accepting fails; a bad connection only ends itself.
+    pub fn serve(&self, listener: TcpListener) -> io::Result<()> {
+        thread::scope(|scope| loop {
+            let (stream, _) = listener.accept()?;
+            scope.spawn(move || {
+                // the client hung up or timed out, nobody left to tell
+                let _ = self.handle(stream);
+            });
+        })
+    }
+
+    fn handle(&self, stream: TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+        let mut reader = BufReader::new(&stream);
+        let (response, head_only) = match read_request(&mut reader)? {
+            Ok((request, host)) => {
+                let head_only = request.method == Method::HEAD;
+                let response = match self.resolve_in(request, host.as_deref()) {
+                    Ok(body) => body.into(),
+                    Err(error) => Response::from(error),
+                };
+                (response, head_only)
+            }
+            Err(error) => (error, false),
+        };
+        write_response(&stream, &response, head_only)
+    }
+}
+
+// the request and its `Host`, or the response to send instead
+type Parsed = Result<(Request, Option<String>), Response>;
+
+fn read_request(reader: &mut impl BufRead) -> io::Result<Parsed> {
+    let bad_request = |message: &str| Err(Response::from(RouteError::BadRequest(message.into())));
+
+    let Some(line) = read_line(reader)? else {
+        return Ok(bad_request("malformed request line"));
+    };
+    let mut parts = line.split_whitespace();
+    let (method, target) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(method), Some(target), Some(version), None)
+            if crate::request::split_target(target).is_ok() && version.starts_with("HTTP/") =>
+        {
+            (method, target)
+        }
+        _ => return Ok(bad_request("malformed request line")),
+    };
+    let method = match Method::try_from(method) {
+        Ok(method) => method,
+        Err(error) => return Ok(Err(Response::from(error))),
+    };
+
+    let mut request = Request::new(method, target);
+    loop {
+        let Some(line) = read_line(reader)? else {
+            return Ok(bad_request("malformed header"));
+        };
+        if line.is_empty() {
+            break;
+        }
+        if request.headers().count() == MAX_HEADERS {
+            return Ok(bad_request("too many headers"));
+        }
+        match line.split_once(':') {
+            Some((name, value)) if !name.is_empty() && !name.contains(' ') => {
+                request = request.with_header(name, value.trim());
+            }
+            _ => return Ok(bad_request("malformed header")),
+        }
+    }
+
+    if let Some(length) = request.header("content-length") {
+        let Ok(length) = length.parse::<usize>() else {
+            return Ok(bad_request("malformed content-length"));
+        };
+        if length > MAX_BODY {
+            return Ok(Err(Response::status(413)));
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body)?;
+        request.body = Some(body);
+    }
+
+    let host = request.header("host").map(String::from);
+    Ok(Ok((request, host)))
+}
+
+// a line without its CRLF, `None` if it's too long, not UTF-8 or cut off
+fn read_line(reader: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut line = vec![];
+    reader.take(MAX_LINE).read_until(b'\n', &mut line)?;
+    if line.pop() != Some(b'\n') {
+        return Ok(None);
+    }
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+    Ok(String::from_utf8(line).ok())
+}
+
+// a HEAD response keeps the `Content-Length` of its body but not the body,
+// which a GET route answering it through `Router::head_fallback` still has
+fn write_response(mut stream: &TcpStream, response: &Response, head_only: bool) -> io::Result<()> {
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\n",
+        response.status,
+        reason(response.status)
+    );
+    for (name, value) in response.headers() {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str(&format!(
+        "Content-Length: {}\r\nConnection: close\r\n\r\n",
+        response.body.len()
+    ));
+    stream.write_all(head.as_bytes())?;
+    if !head_only {
+        stream.write_all(response.body.as_bytes())?;
+    }
+    stream.flush()
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        301 => "Moved Permanently",
+        302 => "Found",
+        304 => "Not Modified",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Content Too Large",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        503 => "Service Unavailable",
+        _ => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Params;
+
+    fn send(addr: std::net::SocketAddr, request: &[u8]) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(request).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn serves_over_tcp() {
+        let router = Router::default()
+            .get("/users/:id", |params: &Params| {
+                format!("user {}", params.get("id").unwrap())
+            })
+            .post("/echo", |request: &Request| {
+                request.body_str().unwrap_or_default().to_string()
+            });
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || router.serve(listener));
+
+        assert_eq!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 6\r\n\
+             Connection: close\r\n\r\nuser 7",
+            send(addr, b"GET /users/7 HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        );
+        assert_eq!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 6\r\n\
+             Connection: close\r\n\r\n",
+            send(addr, b"HEAD /users/7 HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        );
+        assert!(
+            send(addr, b"POST /echo HTTP/1.1\r\nContent-Length: 2\r\n\r\nhi")
+                .ends_with("\r\n\r\nhi")
+        );
+        assert!(send(addr, b"GET /nope HTTP/1.1\r\n\r\n").starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert!(send(addr, b"garbage\r\n\r\n").starts_with("HTTP/1.1 400 Bad Request\r\n"));
+        assert!(send(addr, b"GET / HTTP/1.1\r\nno colon\r\n\r\n").starts_with("HTTP/1.1 400 "));
+        assert!(send(addr, &[0xff, 0xfe, b'\r', b'\n']).starts_with("HTTP/1.1 400 "));
+
+        // hanging up mid-request doesn't take the server down
+        drop(TcpStream::connect(addr).unwrap());
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(b"GET /users/1 HT").unwrap();
+        drop(stream);
+        assert!(send(addr, b"GET /users/8 HTTP/1.1\r\n\r\n").ends_with("user 8"));
+    }
+}
@@ -0,0 +1,186 @@
+// the route table as data, with the `serde` feature, for docs and client
+// generators: serde_json::to_string(&router.to_spec())
+use crate::{Method, MethodMatcher, Router};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RouteSpec {
+    // in registration order, see `Router::routes`
+    pub routes: Vec<RouteSpecEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RouteSpecEntry {
+    // `ANY` for an `any` route
+    pub method: String,
+    // params and wildcards as `{name}`, see `params`
+    pub path: String,
+    // the placeholders in `path`, in order
+    pub params: Vec<String>,
+    pub name: Option<String>,
+    pub host: Option<String>,
+}
+
+impl<T, S> Router<T, S> {
+    pub fn to_spec(&self) -> RouteSpec {
+        RouteSpec {
+            routes: self
+                .nodes
+                .iter()
+                .map(|node| {
+                    let (path, params) = node.pattern.placeholders();
+                    RouteSpecEntry {
+                        method: node.method.as_str().to_string(),
+                        path,
+                        params,
+                        name: node.name.clone(),
+                        host: node.host.clone(),
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    // see `RouteSpec::to_openapi_json`
+    pub fn to_openapi_json(&self) -> String {
+        self.to_spec().to_openapi_json()
+    }
+}
+
+// the methods an OpenAPI path item can have operations for
+const OPENAPI_METHODS: [Method; 8] = [
+    Method::GET,
+    Method::POST,
+    Method::PUT,
+    Method::DELETE,
+    Method::PATCH,
+    Method::HEAD,
+    Method::OPTIONS,
+    Method::TRACE,
+];
+
+impl RouteSpec {
+    // a minimal OpenAPI 3 document: one path item per path, one operation
+    // per method with only the path params and a default response. an `any`
+    // route adds every method not already there, custom methods have no
+    // place in OpenAPI and are left out, as is the host. a named route's
+    // name is its `operationId`.
+    pub fn to_openapi_json(&self) -> String {
+        let mut paths = Map::new();
+        for route in &self.routes {
+            let methods = match Method::try_from(route.method.as_str()) {
+                _ if route.method == MethodMatcher::Any.as_str() => OPENAPI_METHODS.to_vec(),
+                Ok(method) if OPENAPI_METHODS.contains(&method) => vec![method],
+                _ => continue,
+            };
+            let item = paths
+                .entry(route.path.clone())
+                .or_insert_with(|| Value::Object(Map::new()))
+                .as_object_mut()
+                .unwrap();
+            for method in methods {
+                item.entry(method.as_str().to_ascii_lowercase())
+                    .or_insert_with(|| operation(route));
+            }
+        }
+
+        json!({
+            "openapi": "3.0.3",
+            "info": { "title": env!("CARGO_PKG_NAME"), "version": env!("CARGO_PKG_VERSION") },
+            "paths": paths,
+        })
+        .to_string()
+    }
+}
+
+fn operation(route: &RouteSpecEntry) -> Value {
+    let parameters: Vec<Value> = route
+        .params
+        .iter()
+        .map(|name| {
+            json!({
+                "name": name,
+                "in": "path",
+                "required": true,
+                "schema": { "type": "string" },
+            })
+        })
+        .collect();
+    let mut operation = json!({
+        "parameters": parameters,
+        "responses": { "default": { "description": "" } },
+    });
+    if let Some(name) = &route.name {
+        operation["operationId"] = json!(name);
+    }
+    operation
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spec_lists_placeholders() {
+        let ok = || String::from("ok");
+        let router = Router::default()
+            .get("/", ok)
+            .get("/users/*/posts/:post:int", ok)
+            .name("user_post")
+            .route_on_host("static.example.com", Method::GET, "/**/*file", ok);
+
+        let spec = serde_json::to_value(router.to_spec()).unwrap();
+        assert_eq!(
+            json!({
+                "routes": [
+                    { "method": "GET", "path": "/", "params": [], "name": null, "host": null },
+                    {
+                        "method": "GET",
+                        "path": "/users/{param0}/posts/{post}",
+                        "params": ["param0", "post"],
+                        "name": "user_post",
+                        "host": null,
+                    },
+                    {
+                        "method": "GET",
+                        "path": "/{param0}/{file}",
+                        "params": ["param0", "file"],
+                        "name": null,
+                        "host": "static.example.com",
+                    },
+                ]
+            }),
+            spec
+        );
+    }
+
+    #[test]
+    fn openapi_merges_methods_per_path() {
+        let ok = || String::from("ok");
+        let router = Router::default()
+            .get("/users/:id", ok)
+            .name("get_user")
+            .delete("/users/:id", ok)
+            .route(Method::Custom(String::from("PURGE")), "/users/:id", ok)
+            .any("/health", ok);
+
+        let doc: Value = serde_json::from_str(&router.to_openapi_json()).unwrap();
+        assert_eq!("3.0.3", doc["openapi"]);
+        let paths = doc["paths"].as_object().unwrap();
+        assert_eq!(2, paths.len());
+
+        let user = paths["/users/{id}"].as_object().unwrap();
+        assert_eq!(
+            vec!["delete", "get"],
+            user.keys().map(String::as_str).collect::<Vec<_>>()
+        );
+        assert_eq!("get_user", user["get"]["operationId"]);
+        assert!(user["delete"].get("operationId").is_none());
+        assert_eq!(
+            json!([{ "name": "id", "in": "path", "required": true, "schema": { "type": "string" } }]),
+            user["get"]["parameters"]
+        );
+        assert_eq!(8, paths["/health"].as_object().unwrap().len());
+    }
+}
@@ -0,0 +1,238 @@
+// route tests without the string comparing:
+// TestClient::new(&router).get("/users/1").expect_status(200).expect_body("user 1");
+// a failing assertion names the request and prints every route.
+use crate::{Match, Params, RouteError, Router};
+use std::fmt::{self, Write};
+
+pub struct TestClient<'a, T = String, S = ()> {
+    router: &'a Router<T, S>,
+    host: Option<String>,
+}
+
+impl<'a, T, S: Clone> TestClient<'a, T, S> {
+    pub fn new(router: &'a Router<T, S>) -> Self {
+        TestClient { router, host: None }
+    }
+
+    // later requests are sent to `host`, see `Router::route_on_host`
+    pub fn host(&self, host: &str) -> Self {
+        TestClient {
+            router: self.router,
+            host: Some(host.to_string()),
+        }
+    }
+
+    pub fn request(&self, method: &str, path: &str) -> TestResponse<'a, T, S> {
+        TestResponse {
+            router: self.router,
+            method: method.to_string(),
+            path: path.to_string(),
+            result: self.router.dispatch(method, self.host.as_deref(), path),
+        }
+    }
+
+    pub fn get(&self, path: &str) -> TestResponse<'a, T, S> {
+        self.request("GET", path)
+    }
+
+    pub fn post(&self, path: &str) -> TestResponse<'a, T, S> {
+        self.request("POST", path)
+    }
+
+    pub fn put(&self, path: &str) -> TestResponse<'a, T, S> {
+        self.request("PUT", path)
+    }
+
+    pub fn delete(&self, path: &str) -> TestResponse<'a, T, S> {
+        self.request("DELETE", path)
+    }
+}
+
+// the structured result of one request, with panicking assertions that
+// name the request they were made for
+pub struct TestResponse<'a, T = String, S = ()> {
+    // for the route table a failing assertion prints, see `routes`
+    router: &'a Router<T, S>,
+    method: String,
+    path: String,
+    result: Result<Match<T>, RouteError>,
+}
+
+impl<T, S: Clone> TestResponse<'_, T, S> {
+    pub fn result(&self) -> &Result<Match<T>, RouteError> {
+        &self.result
+    }
+
+    // 200 on a match, otherwise `RouteError::status`
+    pub fn status(&self) -> u16 {
+        match &self.result {
+            Ok(_) => 200,
+            Err(error) => error.status(),
+        }
+    }
+
+    // the params of the route that matched, panics without a match
+    #[track_caller]
+    pub fn params(&self) -> &Params {
+        &self.matched().params
+    }
+
+    #[track_caller]
+    pub fn expect_status(self, status: u16) -> Self {
+        assert_eq!(
+            status,
+            self.status(),
+            "{} {}: {}{}",
+            self.method,
+            self.path,
+            self.describe(),
+            self.routes()
+        );
+        self
+    }
+
+    #[track_caller]
+    pub fn expect_not_found(self) -> Self {
+        self.expect_status(404)
+    }
+
+    #[track_caller]
+    pub fn expect_method_not_allowed(self) -> Self {
+        self.expect_status(405)
+    }
+
+    #[track_caller]
+    pub fn expect_pattern(self, pattern: &str) -> Self {
+        let matched = self.matched();
+        assert_eq!(
+            pattern,
+            matched.pattern,
+            "{} {}{}",
+            self.method,
+            self.path,
+            self.routes()
+        );
+        self
+    }
+
+    #[track_caller]
+    pub fn expect_param(self, name: &str, value: &str) -> Self {
+        let matched = self.matched();
+        assert_eq!(
+            Some(value),
+            matched.params.get(name),
+            "{} {}: param `{}`{}",
+            self.method,
+            self.path,
+            name,
+            self.routes()
+        );
+        self
+    }
+
+    #[track_caller]
+    fn matched(&self) -> &Match<T> {
+        match &self.result {
+            Ok(matched) => matched,
+            Err(error) => panic!(
+                "{} {}: expected a match, got `{}`{}",
+                self.method,
+                self.path,
+                error,
+                self.routes()
+            ),
+        }
+    }
+
+    // every route of the router and its fallbacks, one per line, in the
+    // order a `Match::index` counts them. only built for a failure.
+    fn routes(&self) -> String {
+        let mut table = String::from("\nroutes:");
+        let mut next = Some(self.router);
+        while let Some(router) = next {
+            for node in router.routes() {
+                write!(table, "\n    {}", node).unwrap();
+            }
+            next = router.fallback.as_deref();
+        }
+        if table.ends_with(':') {
+            table.push_str(" none");
+        }
+        table
+    }
+
+    fn describe(&self) -> String {
+        match &self.result {
+            Ok(matched) => format!("matched `{}`", matched.pattern),
+            Err(error) => error.to_string(),
+        }
+    }
+}
+
+impl<T: fmt::Debug, S: Clone> TestResponse<'_, T, S> {
+    #[track_caller]
+    pub fn expect_body<B>(self, body: B) -> Self
+    where
+        T: PartialEq<B>,
+        B: fmt::Debug,
+    {
+        let matched = self.matched();
+        assert!(
+            matched.body == body,
+            "{} {}: expected body {:?}, got {:?}{}",
+            self.method,
+            self.path,
+            body,
+            matched.body,
+            self.routes()
+        );
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Method, Params};
+
+    #[test]
+    fn test_client_asserts_on_results() {
+        let router = Router::default()
+            .get("/users/:id", |params: &Params| {
+                format!("user {}", params.get("id").unwrap())
+            })
+            .route_on_host("api.example.com", Method::GET, "/", || String::from("api"));
+        let client = TestClient::new(&router);
+
+        client
+            .get("/users/1")
+            .expect_status(200)
+            .expect_pattern("/users/:id")
+            .expect_param("id", "1")
+            .expect_body("user 1");
+        assert_eq!(Some("2"), client.get("/users/2").params().get("id"));
+        client.get("/posts").expect_not_found();
+        client.delete("/users/1").expect_method_not_allowed();
+        client.request("GE T", "/").expect_status(400);
+        client.host("api.example.com").get("/").expect_body("api");
+    }
+
+    #[test]
+    #[should_panic(expected = "GET /posts: expected a match, got `no match routes`")]
+    fn test_client_names_the_failing_request() {
+        let router = Router::default().get("/", || String::from("home"));
+        TestClient::new(&router).get("/posts").expect_body("home");
+    }
+
+    #[test]
+    #[should_panic(expected = "routes:\n    GET /\n    POST /posts\n    GET /about")]
+    fn test_client_prints_the_route_table() {
+        let router = Router::default()
+            .get("/", || String::from("home"))
+            .post("/posts", || String::from("created"))
+            .or_else(Router::default().get("/about", || String::from("about")));
+        TestClient::new(&router)
+            .get("/posts")
+            .expect_pattern("/posts");
+    }
+}